@@ -6,9 +6,9 @@ fn main() {
         .add_plugins(FullGame)
         .insert_resource(ClearColor(Color::MIDNIGHT_BLUE * 0.1))
         .insert_resource(SpacecraftConfig {
-            stereo_enabled: false,
+            stereo_mode: StereoMode::Off,
             start_transform: Transform::from_xyz(0.0, 0.0, 100.0).looking_at(Vec3::ZERO, Vec3::Y),
-            impact_magnitude: 5.0,
+            impact_scale: 1.0,
             ..Default::default()
         })
         .insert_resource(PhysicsConfig {
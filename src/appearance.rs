@@ -0,0 +1,332 @@
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use rand::Rng;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use crate::clock::SimulationClock;
+use crate::noise::{fbm, smoothstep};
+use crate::physics::Momentum;
+use crate::radar::player_color;
+use crate::scoring::{LocalPlayer, Owner};
+
+// Linear RGB.
+const ROCK: Vec3 = Vec3::new(0.3, 0.2, 0.13);
+const SHALLOWS: Vec3 = Vec3::new(0.05, 0.3, 0.35);
+const DEEP: Vec3 = Vec3::new(0.01, 0.03, 0.15);
+const ICE: Vec3 = Vec3::new(0.85, 0.9, 0.95);
+const CREAM: Vec3 = Vec3::new(0.8, 0.65, 0.45);
+
+// Clouds float this far out, as a multiple of the planet's radius.
+const CLOUD_HEIGHT: f32 = 1.02;
+// How brightly an owned planet glows in its owner's color.
+const OWNER_GLOW: f32 = 0.08;
+
+#[derive(Resource)]
+pub struct AppearanceConfig {
+    /// Width of the generated surface textures. They are half as tall.
+    pub texture_width: u32,
+    pub clouds: bool,
+    /// Seas are glossy and land is matte. Without this everything is matte.
+    pub water_specularity: bool,
+    /// Radians per second.
+    pub cloud_drift: f32,
+}
+
+impl Default for AppearanceConfig {
+    fn default() -> Self {
+        Self {
+            texture_width: 256,
+            clouds: true,
+            water_specularity: true,
+            cloud_drift: 0.02,
+        }
+    }
+}
+
+/// What a planet is made of, as fractions of its mass that add up to 1.0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Composition {
+    pub rock: f32,
+    pub water: f32,
+    pub gas: f32,
+}
+
+impl Composition {
+    pub fn new(rock: f32, water: f32, gas: f32) -> Self {
+        let total = rock + water + gas;
+        if total <= 0.0 {
+            return Self::default();
+        }
+        Self {
+            rock: rock / total,
+            water: water / total,
+            gas: gas / total,
+        }
+    }
+}
+
+impl Default for Composition {
+    fn default() -> Self {
+        Self {
+            rock: 1.0,
+            water: 0.0,
+            gas: 0.0,
+        }
+    }
+}
+
+/// How a planet looks. Its surface is generated from this and its mass, so the same appearance
+/// always looks the same, on every client.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Appearance {
+    pub seed: u64,
+    pub composition: Composition,
+    /// Colors the rock and the gas, which are otherwise dull browns and creams.
+    pub tint: Color,
+}
+
+impl Default for Appearance {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            composition: Composition::default(),
+            tint: Color::GRAY,
+        }
+    }
+}
+
+impl Appearance {
+    pub fn random(rng: &mut impl Rng) -> Self {
+        Self {
+            seed: rng.gen(),
+            composition: Composition::new(
+                rng.gen(),
+                rng.gen::<f32>() * 0.8,
+                rng.gen::<f32>().powf(2.0),
+            ),
+            tint: Color::rgb(rng.gen(), rng.gen(), rng.gen()),
+        }
+    }
+
+    /// Take in a planet that makes up `fraction` of the merged mass. The seed, and with it the
+    /// lie of the land, stays ours.
+    pub fn absorb(&mut self, minor: &Appearance, fraction: f32) {
+        let mix = |ours: f32, theirs: f32| ours + (theirs - ours) * fraction;
+        let (ours, theirs) = (self.composition, minor.composition);
+        self.composition = Composition::new(
+            mix(ours.rock, theirs.rock),
+            mix(ours.water, theirs.water),
+            mix(ours.gas, theirs.gas),
+        );
+        let [r, g, b, _] = self.tint.as_rgba_f32();
+        let [their_r, their_g, their_b, _] = minor.tint.as_rgba_f32();
+        self.tint = Color::rgb(mix(r, their_r), mix(g, their_g), mix(b, their_b));
+    }
+}
+
+pub struct PlanetTextures {
+    pub surface: Image,
+    /// Roughness in green, as `StandardMaterial::metallic_roughness_texture` wants it.
+    pub roughness: Image,
+    /// None for planets too dry to have any.
+    pub clouds: Option<Image>,
+}
+
+/// Equirectangular textures for a `shape::UVSphere`.
+pub fn planet_textures(
+    appearance: &Appearance,
+    mass: f32,
+    config: &AppearanceConfig,
+) -> PlanetTextures {
+    let (width, height) = (config.texture_width, config.texture_width / 2);
+    let Appearance {
+        seed,
+        composition: Composition { water, gas, .. },
+        tint,
+    } = *appearance;
+    let tint = Vec4::from(tint.as_linear_rgba_f32()).truncate();
+    // Big planets hold on to more of their atmosphere.
+    let banding = (gas * (0.5 + smoothstep(20.0, 300.0, mass))).min(1.0);
+    // Roughly the share of the surface under water.
+    let sea_level = (water * 1.1).min(0.9);
+    let ice_line = 0.97 - water * 0.12;
+    let cloud_cover = if config.clouds {
+        0.5 * water + 0.3 * gas
+    } else {
+        0.0
+    };
+    let cloud_line = 0.75 - cloud_cover * 0.4;
+    let rock = ROCK.lerp(tint, 0.4);
+    let bands = [CREAM.lerp(tint, 0.3), tint * 0.6];
+
+    let texel_count = (width * height) as usize;
+    let mut surface = Vec::with_capacity(texel_count * 4);
+    let mut roughness = Vec::with_capacity(texel_count * 4);
+    let mut clouds = Vec::with_capacity(texel_count * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let direction = texel_direction(
+                (x as f32 + 0.5) / width as f32,
+                (y as f32 + 0.5) / height as f32,
+            );
+            // Stretched out so that it is spread fairly evenly from 0.0 to 1.0.
+            let elevation = smoothstep(0.3, 0.7, fbm(seed, direction * 2.5, 5));
+            let (ground, wet) = if elevation < sea_level {
+                let depth = (sea_level - elevation) / sea_level;
+                (SHALLOWS.lerp(DEEP, smoothstep(0.0, 0.4, depth)), true)
+            } else {
+                let height = (elevation - sea_level) / (1.0 - sea_level);
+                (rock * (0.5 + height), false)
+            };
+            // Ice caps, further from the poles on wetter planets.
+            let ice = if water > 0.05 {
+                let ragged = direction.z.abs() + (fbm(seed + 3, direction * 8.0, 2) - 0.5) * 0.1;
+                smoothstep(ice_line, ice_line + 0.04, ragged)
+            } else {
+                0.0
+            };
+            let ground = ground.lerp(ICE, ice);
+            // Bands of latitude, stirred up by turbulence.
+            let turbulence = (fbm(seed + 4, direction * 3.0, 4) - 0.5) * 4.0;
+            let band = (direction.z * 12.0 + turbulence).sin() * 0.5 + 0.5;
+            let color = ground.lerp(bands[0].lerp(bands[1], band), banding);
+            surface.extend(Color::rgb_linear(color.x, color.y, color.z).as_rgba_u8());
+
+            let glossy = wet && ice < 0.5 && config.water_specularity;
+            let rough = if glossy { 0.15 } else { 0.9 };
+            let rough = rough + (0.6 - rough) * banding;
+            roughness.extend([0, (rough * 255.0) as u8, 0, 255]);
+
+            let cloud = smoothstep(
+                cloud_line,
+                cloud_line + 0.15,
+                fbm(seed + 7, direction * 4.0, 5),
+            );
+            clouds.extend([255, 255, 255, (cloud * 0.9 * 255.0) as u8]);
+        }
+    }
+
+    let image = |data, format| {
+        Image::new(
+            Extent3d {
+                width,
+                height,
+                ..default()
+            },
+            TextureDimension::D2,
+            data,
+            format,
+        )
+    };
+    PlanetTextures {
+        surface: image(surface, TextureFormat::Rgba8UnormSrgb),
+        roughness: image(roughness, TextureFormat::Rgba8Unorm),
+        clouds: (cloud_cover > 0.05).then(|| image(clouds, TextureFormat::Rgba8UnormSrgb)),
+    }
+}
+
+// Where a texture coordinate lands on a `shape::UVSphere`, which has its poles on z.
+fn texel_direction(u: f32, v: f32) -> Vec3 {
+    let latitude = FRAC_PI_2 - v * PI;
+    let longitude = u * TAU;
+    Vec3::new(
+        latitude.cos() * longitude.cos(),
+        latitude.cos() * longitude.sin(),
+        latitude.sin(),
+    )
+}
+
+#[derive(Component)]
+pub struct CloudLayer;
+
+type NewAppearances<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Appearance,
+        &'static Momentum,
+        &'static Handle<StandardMaterial>,
+        &'static Handle<Mesh>,
+        Option<&'static Children>,
+    ),
+    Changed<Appearance>,
+>;
+
+/// Generate the surfaces of new planets, and of planets that have changed in a merge.
+pub fn paint_planets(
+    mut commands: Commands,
+    planet_query: NewAppearances,
+    cloud_query: Query<(), With<CloudLayer>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    config: Res<AppearanceConfig>,
+) {
+    for (planet, appearance, momentum, material, mesh, children) in planet_query.iter() {
+        debug!("Painting planet {planet:?}: {appearance:?}");
+        let textures = planet_textures(appearance, momentum.mass, &config);
+        if let Some(material) = materials.get_mut(material) {
+            material.base_color = Color::WHITE;
+            material.base_color_texture = Some(images.add(textures.surface));
+            material.metallic_roughness_texture = Some(images.add(textures.roughness));
+            material.perceptual_roughness = 1.0;
+        }
+        for &child in children.into_iter().flatten() {
+            if cloud_query.contains(child) {
+                commands.entity(child).despawn_recursive();
+            }
+        }
+        if let Some(clouds) = textures.clouds {
+            let layer = commands
+                .spawn(PbrBundle {
+                    // The planet's own mesh, so the clouds grow with it.
+                    mesh: mesh.clone(),
+                    material: materials.add(StandardMaterial {
+                        base_color_texture: Some(images.add(clouds)),
+                        alpha_mode: AlphaMode::Blend,
+                        perceptual_roughness: 1.0,
+                        ..default()
+                    }),
+                    transform: Transform::from_scale(Vec3::splat(CLOUD_HEIGHT)),
+                    ..default()
+                })
+                .insert(CloudLayer)
+                .id();
+            commands.entity(planet).add_child(layer);
+        }
+    }
+}
+
+pub fn drift_clouds(
+    mut cloud_query: Query<&mut Transform, With<CloudLayer>>,
+    config: Res<AppearanceConfig>,
+    clock: Res<SimulationClock>,
+) {
+    for mut transform in cloud_query.iter_mut() {
+        transform.rotate_local_z(config.cloud_drift * clock.delta_seconds());
+    }
+}
+
+/// Owned planets glow faintly: ours green, everybody else's in their own color, like the radar.
+pub fn show_planet_owner(
+    planet_query: Query<(Ref<Owner>, &Handle<StandardMaterial>)>,
+    local_player: Res<LocalPlayer>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (owner, material) in planet_query.iter() {
+        if !owner.is_changed() && !local_player.is_changed() {
+            continue;
+        }
+        let glow = match owner.player {
+            None => Color::BLACK,
+            Some(player) if player == local_player.0 => Color::GREEN,
+            Some(player) => {
+                let color = player_color(player);
+                Color::rgb_u8(color.r(), color.g(), color.b())
+            }
+        };
+        if let Some(material) = materials.get_mut(material) {
+            material.emissive = glow * OWNER_GLOW;
+        }
+    }
+}
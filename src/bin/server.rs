@@ -0,0 +1,28 @@
+use bevy::prelude::{App, Startup};
+use mass_gathering::prelude::{my_planets, PhysicsConfig, DEFAULT_PORT};
+use mass_gathering::DedicatedServer;
+use std::net::{Ipv4Addr, SocketAddr};
+
+fn main() {
+    // `--bind <address>` overrides the default of listening on all interfaces.
+    let bind = std::env::args()
+        .skip_while(|arg| arg != "--bind")
+        .nth(1)
+        .map(|address| {
+            address
+                .parse()
+                .expect("Expected a bind address like 127.0.0.1:5151")
+        })
+        .unwrap_or(SocketAddr::from((Ipv4Addr::UNSPECIFIED, DEFAULT_PORT)));
+    App::new()
+        .insert_resource(PhysicsConfig {
+            sims_per_frame: 1,
+            trails: false,
+            trail_ttl: 0,
+            // Nobody is watching.
+            merge_seconds: 0.0,
+        })
+        .add_plugins(DedicatedServer { bind })
+        .add_systems(Startup, my_planets)
+        .run();
+}
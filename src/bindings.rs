@@ -0,0 +1,1092 @@
+use bevy::ecs::system::SystemParam;
+use bevy::input::mouse::{MouseButton, MouseMotion, MouseWheel};
+use bevy::prelude::*;
+use bevy::reflect::DynamicEnum;
+use bevy_egui::{
+    egui::{self, Align2, Color32, ComboBox, DragValue, Grid, RichText, ScrollArea, Window},
+    EguiContexts,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::{config_dir, AppState};
+
+const BINDINGS_FILE: &str = "input_bindings.cfg";
+
+// A stick must be pushed at least this far to be captured as a new binding.
+const CAPTURE_THRESHOLD: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Action {
+    Pitch,
+    Yaw,
+    Roll,
+    Fire,
+    Throttle,
+    Strafe,
+    Lift,
+    FlightAssist,
+    MatchVelocity,
+    Approach,
+    Orbit,
+    NextWeapon,
+    LockTarget,
+    NextTarget,
+    PreviousTarget,
+    TargetSort,
+    CameraMode,
+    StereoMode,
+    StereoSeparation,
+    StereoConvergence,
+    SlowDown,
+    SpeedUp,
+    PauseTime,
+    StepTime,
+    Rewind,
+    Replay,
+    Help,
+    Inspector,
+}
+
+impl Action {
+    pub const ALL: [Action; 28] = [
+        Action::Fire,
+        Action::NextWeapon,
+        Action::LockTarget,
+        Action::NextTarget,
+        Action::PreviousTarget,
+        Action::TargetSort,
+        Action::CameraMode,
+        Action::StereoMode,
+        Action::Pitch,
+        Action::Yaw,
+        Action::Roll,
+        Action::Throttle,
+        Action::Strafe,
+        Action::Lift,
+        Action::FlightAssist,
+        Action::MatchVelocity,
+        Action::Approach,
+        Action::Orbit,
+        Action::SlowDown,
+        Action::SpeedUp,
+        Action::PauseTime,
+        Action::StepTime,
+        Action::Rewind,
+        Action::Replay,
+        Action::StereoSeparation,
+        Action::StereoConvergence,
+        Action::Help,
+        Action::Inspector,
+    ];
+
+    // Flying (and stereo tuning) is done with axes. Everything else is a button.
+    pub fn is_axis(&self) -> bool {
+        matches!(
+            self,
+            Action::Pitch
+                | Action::Yaw
+                | Action::Roll
+                | Action::Throttle
+                | Action::Strafe
+                | Action::Lift
+                | Action::StereoSeparation
+                | Action::StereoConvergence
+        )
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::Pitch => "Pitch up / Pitch down",
+            Action::Yaw => "Yaw left / Yaw right",
+            Action::Roll => "Roll left / Roll right",
+            Action::Fire => "Fire Projectile",
+            Action::NextWeapon => "Next projectile type",
+            Action::LockTarget => "Lock / unlock target",
+            Action::NextTarget => "Next target",
+            Action::PreviousTarget => "Previous target",
+            Action::TargetSort => "Cycle target order (nearest, largest, most threatening)",
+            Action::CameraMode => "Next camera view",
+            Action::StereoMode => "Next stereo mode",
+            Action::StereoSeparation => "Widen / narrow stereo separation",
+            Action::StereoConvergence => "Push out / pull in stereo convergence",
+            Action::Throttle => "Throttle up / Throttle down",
+            Action::Strafe => "Strafe right / Strafe left",
+            Action::Lift => "Thrust up / Thrust down",
+            Action::FlightAssist => "Toggle flight assist",
+            Action::MatchVelocity => "Autopilot: match velocity with target",
+            Action::Approach => "Autopilot: approach target",
+            Action::Orbit => "Autopilot: orbit target",
+            Action::SlowDown => "Slow time down",
+            Action::SpeedUp => "Speed time up",
+            Action::PauseTime => "Pause / resume time",
+            Action::StepTime => "Step time forward one frame",
+            Action::Rewind => "Rewind a few seconds",
+            Action::Replay => "Instant replay of the last merge",
+            Action::Help => "Pause and show this help screen",
+            Action::Inspector => "Open / close the developer inspector",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Action::Pitch => "pitch",
+            Action::Yaw => "yaw",
+            Action::Roll => "roll",
+            Action::Fire => "fire",
+            Action::NextWeapon => "next_weapon",
+            Action::LockTarget => "lock_target",
+            Action::NextTarget => "next_target",
+            Action::PreviousTarget => "previous_target",
+            Action::TargetSort => "target_sort",
+            Action::CameraMode => "camera_mode",
+            Action::StereoMode => "stereo_mode",
+            Action::StereoSeparation => "stereo_separation",
+            Action::StereoConvergence => "stereo_convergence",
+            Action::Throttle => "throttle",
+            Action::Strafe => "strafe",
+            Action::Lift => "lift",
+            Action::FlightAssist => "flight_assist",
+            Action::MatchVelocity => "match_velocity",
+            Action::Approach => "approach",
+            Action::Orbit => "orbit",
+            Action::SlowDown => "slow_down",
+            Action::SpeedUp => "speed_up",
+            Action::PauseTime => "pause_time",
+            Action::StepTime => "step_time",
+            Action::Rewind => "rewind",
+            Action::Replay => "replay",
+            Action::Help => "help",
+            Action::Inspector => "inspector",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputSource {
+    Key(KeyCode),
+    // An axis driven by two keys.
+    KeyPair {
+        positive: KeyCode,
+        negative: KeyCode,
+    },
+    MouseButton(MouseButton),
+    MouseX,
+    MouseY,
+    MouseWheel,
+    GamepadAxis(GamepadAxisType),
+    GamepadButton(GamepadButtonType),
+}
+
+impl InputSource {
+    pub fn is_keyboard(&self) -> bool {
+        matches!(self, InputSource::Key(_) | InputSource::KeyPair { .. })
+    }
+
+    pub fn is_mouse(&self) -> bool {
+        matches!(
+            self,
+            InputSource::MouseButton(_)
+                | InputSource::MouseX
+                | InputSource::MouseY
+                | InputSource::MouseWheel
+        )
+    }
+
+    pub fn is_gamepad(&self) -> bool {
+        matches!(
+            self,
+            InputSource::GamepadAxis(_) | InputSource::GamepadButton(_)
+        )
+    }
+
+    // Mouse movement and the wheel report how far they moved this frame, not how far they are held.
+    fn is_relative(&self) -> bool {
+        matches!(
+            self,
+            InputSource::MouseX | InputSource::MouseY | InputSource::MouseWheel
+        )
+    }
+}
+
+impl fmt::Display for InputSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputSource::Key(key) => write!(f, "{key:?}"),
+            InputSource::KeyPair { positive, negative } => write!(f, "{positive:?} / {negative:?}"),
+            InputSource::MouseButton(button) => write!(f, "{button:?} click"),
+            InputSource::MouseX => write!(f, "Left / Right"),
+            InputSource::MouseY => write!(f, "Up / Down"),
+            InputSource::MouseWheel => write!(f, "Scroll wheel"),
+            InputSource::GamepadAxis(axis) => write!(f, "{axis:?}"),
+            InputSource::GamepadButton(button) => write!(f, "{button:?}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Binding {
+    pub source: InputSource,
+    /// Radians (or units of speed) per second for held inputs, per unit of movement for the mouse.
+    pub sensitivity: f32,
+    pub invert: bool,
+    /// Stick deflection below this is ignored.
+    pub deadzone: f32,
+}
+
+impl Binding {
+    pub fn new(source: InputSource, sensitivity: f32) -> Self {
+        Self {
+            source,
+            sensitivity,
+            invert: false,
+            deadzone: 0.0,
+        }
+    }
+
+    pub fn inverted(self) -> Self {
+        Self {
+            invert: true,
+            ..self
+        }
+    }
+
+    pub fn with_deadzone(self, deadzone: f32) -> Self {
+        Self { deadzone, ..self }
+    }
+
+    // Sensitivity is a property of axes. Buttons are just on or off.
+    fn default_sensitivity(action: Action, source: &InputSource) -> f32 {
+        match (action, source) {
+            (action, _) if !action.is_axis() => 1.0,
+            (Action::Throttle, InputSource::MouseWheel) => 0.1,
+            (Action::Throttle, _) => 0.5,
+            (Action::Strafe | Action::Lift, _) => 1.0,
+            (Action::StereoSeparation | Action::StereoConvergence, _) => 0.5,
+            (_, InputSource::MouseX | InputSource::MouseY) => 0.001,
+            (_, InputSource::GamepadAxis(_)) => 1.5,
+            _ => 0.377,
+        }
+    }
+}
+
+/// The action map: which inputs drive which actions.
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct InputBindings {
+    pub bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        use InputSource::*;
+        let bindings = HashMap::from([
+            (
+                Action::Pitch,
+                vec![
+                    Binding::new(
+                        KeyPair {
+                            positive: KeyCode::W,
+                            negative: KeyCode::S,
+                        },
+                        0.377,
+                    ),
+                    Binding::new(MouseY, 0.001).inverted(),
+                    Binding::new(GamepadAxis(GamepadAxisType::LeftStickY), 1.5).with_deadzone(0.15),
+                ],
+            ),
+            (
+                Action::Yaw,
+                vec![
+                    Binding::new(
+                        KeyPair {
+                            positive: KeyCode::A,
+                            negative: KeyCode::D,
+                        },
+                        0.377,
+                    ),
+                    Binding::new(MouseX, 0.001).inverted(),
+                    Binding::new(GamepadAxis(GamepadAxisType::LeftStickX), 1.5)
+                        .inverted()
+                        .with_deadzone(0.15),
+                ],
+            ),
+            (
+                Action::Roll,
+                vec![
+                    Binding::new(
+                        KeyPair {
+                            positive: KeyCode::Z,
+                            negative: KeyCode::X,
+                        },
+                        0.377,
+                    ),
+                    Binding::new(GamepadAxis(GamepadAxisType::RightStickX), 1.5)
+                        .inverted()
+                        .with_deadzone(0.15),
+                ],
+            ),
+            (
+                Action::Fire,
+                vec![
+                    Binding::new(Key(KeyCode::Space), 1.0),
+                    Binding::new(MouseButton(bevy::input::mouse::MouseButton::Left), 1.0),
+                    Binding::new(GamepadButton(GamepadButtonType::RightTrigger2), 1.0),
+                ],
+            ),
+            (
+                Action::NextWeapon,
+                vec![
+                    Binding::new(Key(KeyCode::Tab), 1.0),
+                    Binding::new(GamepadButton(GamepadButtonType::RightTrigger), 1.0),
+                ],
+            ),
+            (
+                Action::LockTarget,
+                vec![
+                    Binding::new(Key(KeyCode::L), 1.0),
+                    Binding::new(GamepadButton(GamepadButtonType::LeftTrigger), 1.0),
+                ],
+            ),
+            (
+                Action::NextTarget,
+                vec![Binding::new(Key(KeyCode::BracketRight), 1.0)],
+            ),
+            (
+                Action::PreviousTarget,
+                vec![Binding::new(Key(KeyCode::BracketLeft), 1.0)],
+            ),
+            (Action::TargetSort, vec![Binding::new(Key(KeyCode::K), 1.0)]),
+            (
+                Action::CameraMode,
+                vec![
+                    Binding::new(Key(KeyCode::F5), 1.0),
+                    Binding::new(GamepadButton(GamepadButtonType::Select), 1.0),
+                ],
+            ),
+            (
+                Action::StereoMode,
+                vec![Binding::new(Key(KeyCode::F6), 1.0)],
+            ),
+            (
+                Action::StereoSeparation,
+                vec![Binding::new(
+                    KeyPair {
+                        positive: KeyCode::Equals,
+                        negative: KeyCode::Minus,
+                    },
+                    0.5,
+                )],
+            ),
+            (
+                Action::StereoConvergence,
+                vec![Binding::new(
+                    KeyPair {
+                        positive: KeyCode::Period,
+                        negative: KeyCode::Comma,
+                    },
+                    0.5,
+                )],
+            ),
+            (
+                Action::Throttle,
+                vec![
+                    Binding::new(
+                        KeyPair {
+                            positive: KeyCode::PageUp,
+                            negative: KeyCode::PageDown,
+                        },
+                        0.5,
+                    ),
+                    Binding::new(MouseWheel, 0.1),
+                    Binding::new(GamepadAxis(GamepadAxisType::RightStickY), 0.5)
+                        .with_deadzone(0.15),
+                ],
+            ),
+            (
+                Action::Strafe,
+                vec![
+                    Binding::new(
+                        KeyPair {
+                            positive: KeyCode::Right,
+                            negative: KeyCode::Left,
+                        },
+                        1.0,
+                    ),
+                    Binding::new(GamepadButton(GamepadButtonType::DPadRight), 1.0),
+                    Binding::new(GamepadButton(GamepadButtonType::DPadLeft), 1.0).inverted(),
+                ],
+            ),
+            (
+                Action::Lift,
+                vec![
+                    Binding::new(
+                        KeyPair {
+                            positive: KeyCode::Up,
+                            negative: KeyCode::Down,
+                        },
+                        1.0,
+                    ),
+                    Binding::new(GamepadButton(GamepadButtonType::DPadUp), 1.0),
+                    Binding::new(GamepadButton(GamepadButtonType::DPadDown), 1.0).inverted(),
+                ],
+            ),
+            (
+                Action::FlightAssist,
+                vec![
+                    Binding::new(Key(KeyCode::F), 1.0),
+                    Binding::new(GamepadButton(GamepadButtonType::North), 1.0),
+                ],
+            ),
+            (
+                Action::MatchVelocity,
+                vec![
+                    Binding::new(Key(KeyCode::V), 1.0),
+                    Binding::new(GamepadButton(GamepadButtonType::West), 1.0),
+                ],
+            ),
+            (
+                Action::Approach,
+                vec![
+                    Binding::new(Key(KeyCode::C), 1.0),
+                    Binding::new(GamepadButton(GamepadButtonType::East), 1.0),
+                ],
+            ),
+            (
+                Action::Orbit,
+                vec![
+                    Binding::new(Key(KeyCode::O), 1.0),
+                    Binding::new(GamepadButton(GamepadButtonType::South), 1.0),
+                ],
+            ),
+            (
+                Action::SlowDown,
+                vec![Binding::new(Key(KeyCode::Key9), 1.0)],
+            ),
+            (Action::SpeedUp, vec![Binding::new(Key(KeyCode::Key0), 1.0)]),
+            (
+                Action::PauseTime,
+                vec![Binding::new(Key(KeyCode::Key8), 1.0)],
+            ),
+            (
+                Action::StepTime,
+                vec![Binding::new(Key(KeyCode::Key7), 1.0)],
+            ),
+            (Action::Rewind, vec![Binding::new(Key(KeyCode::Back), 1.0)]),
+            (Action::Replay, vec![Binding::new(Key(KeyCode::R), 1.0)]),
+            (
+                Action::Help,
+                vec![
+                    Binding::new(Key(KeyCode::H), 1.0),
+                    Binding::new(Key(KeyCode::P), 1.0),
+                ],
+            ),
+            (
+                Action::Inspector,
+                vec![Binding::new(Key(KeyCode::F12), 1.0)],
+            ),
+        ]);
+        Self { bindings }
+    }
+}
+
+impl InputBindings {
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn path() -> PathBuf {
+        config_dir().join(BINDINGS_FILE)
+    }
+
+    /// Read the user's bindings over the defaults, so actions added since they last saved still
+    /// have a binding.
+    pub fn load() -> Self {
+        let mut bindings = Self::default();
+        let path = Self::path();
+        if let Ok(text) = std::fs::read_to_string(&path) {
+            info!("Loading input bindings from {path:?}");
+            bindings.parse(&text);
+        }
+        bindings
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        info!("Saving input bindings to {path:?}");
+        std::fs::write(path, self.to_string())
+    }
+
+    /// One binding per line: `<action> <source> [sensitivity=<n>] [invert] [deadzone=<n>]`, or
+    /// `<action> none` for an action with no bindings at all. Lines that do not parse are skipped
+    /// with a warning. Every action mentioned loses the bindings it had, and actions that are not
+    /// mentioned are left as they are.
+    pub fn parse(&mut self, text: &str) {
+        let mut mentioned = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((action, binding)) = parse_line(line) else {
+                warn!("Ignoring input binding on line {}: {line:?}", number + 1);
+                continue;
+            };
+            let bindings = self.bindings.entry(action).or_default();
+            if !mentioned.contains(&action) {
+                mentioned.push(action);
+                bindings.clear();
+            }
+            bindings.extend(binding);
+        }
+    }
+}
+
+impl fmt::Display for InputBindings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "# <action> <source> [sensitivity=<n>] [invert] [deadzone=<n>]"
+        )?;
+        for action in Action::ALL {
+            if self.get(action).is_empty() {
+                writeln!(f, "{} none", action.name())?;
+            }
+            for binding in self.get(action) {
+                write!(f, "{} ", action.name())?;
+                match binding.source {
+                    InputSource::Key(key) => write!(f, "key {key:?}")?,
+                    InputSource::KeyPair { positive, negative } => {
+                        write!(f, "keys {positive:?} {negative:?}")?
+                    }
+                    InputSource::MouseButton(button) => write!(f, "mouse_button {button:?}")?,
+                    InputSource::MouseX => write!(f, "mouse_x")?,
+                    InputSource::MouseY => write!(f, "mouse_y")?,
+                    InputSource::MouseWheel => write!(f, "mouse_wheel")?,
+                    InputSource::GamepadAxis(axis) => write!(f, "gamepad_axis {axis:?}")?,
+                    InputSource::GamepadButton(button) => write!(f, "gamepad_button {button:?}")?,
+                }
+                write!(f, " sensitivity={}", binding.sensitivity)?;
+                if binding.invert {
+                    write!(f, " invert")?;
+                }
+                if binding.deadzone != 0.0 {
+                    write!(f, " deadzone={}", binding.deadzone)?;
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// The action, and its binding unless it has none.
+fn parse_line(line: &str) -> Option<(Action, Option<Binding>)> {
+    let mut words = line.split_whitespace();
+    let name = words.next()?;
+    let action = Action::ALL
+        .into_iter()
+        .find(|action| action.name() == name)?;
+    let source = match words.next()? {
+        "none" => return words.next().is_none().then_some((action, None)),
+        "key" => InputSource::Key(variant(words.next()?)?),
+        "keys" => InputSource::KeyPair {
+            positive: variant(words.next()?)?,
+            negative: variant(words.next()?)?,
+        },
+        "mouse_button" => InputSource::MouseButton(variant(words.next()?)?),
+        "mouse_x" => InputSource::MouseX,
+        "mouse_y" => InputSource::MouseY,
+        "mouse_wheel" => InputSource::MouseWheel,
+        "gamepad_axis" => InputSource::GamepadAxis(variant(words.next()?)?),
+        "gamepad_button" => InputSource::GamepadButton(variant(words.next()?)?),
+        _ => return None,
+    };
+    let mut binding = Binding::new(source, Binding::default_sensitivity(action, &source));
+    for word in words {
+        match word.split_once('=') {
+            Some(("sensitivity", value)) => binding.sensitivity = value.parse().ok()?,
+            Some(("deadzone", value)) => binding.deadzone = value.parse().ok()?,
+            None if word == "invert" => binding.invert = true,
+            _ => return None,
+        }
+    }
+    Some((action, Some(binding)))
+}
+
+// Bevy's input enums are reflected, so their variant names (as printed by `Debug`) parse back.
+fn variant<T: FromReflect>(name: &str) -> Option<T> {
+    T::from_reflect(&DynamicEnum::new(name, ()))
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ActionValue {
+    /// Summed from held inputs. Scale this by the frame time.
+    pub rate: f32,
+    /// Summed from mouse movement this frame. Use as is.
+    pub delta: f32,
+    pub pressed: bool,
+    /// Pressed this frame, but not the last.
+    pub just_pressed: bool,
+}
+
+impl ActionValue {
+    pub fn is_active(&self) -> bool {
+        self.rate != 0.0 || self.delta != 0.0 || self.pressed
+    }
+}
+
+/// This frame's value of every action, as read through `InputBindings`.
+#[derive(Resource, Debug, Default)]
+pub struct ActionState(HashMap<Action, ActionValue>);
+
+impl ActionState {
+    pub fn get(&self, action: Action) -> ActionValue {
+        self.0.get(&action).copied().unwrap_or_default()
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.get(action).pressed
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.get(action).just_pressed
+    }
+}
+
+#[derive(SystemParam)]
+pub struct RawInputs<'w, 's> {
+    keys: Res<'w, Input<KeyCode>>,
+    mouse_buttons: Res<'w, Input<MouseButton>>,
+    mouse_motion_events: EventReader<'w, 's, MouseMotion>,
+    mouse_wheel_events: EventReader<'w, 's, MouseWheel>,
+    gamepads: Res<'w, Gamepads>,
+    gamepad_axes: Res<'w, Axis<GamepadAxis>>,
+    gamepad_buttons: Res<'w, Input<GamepadButton>>,
+}
+
+impl RawInputs<'_, '_> {
+    // The strongest deflection of `axis` on any connected gamepad.
+    fn gamepad_axis(&self, axis_type: GamepadAxisType) -> f32 {
+        self.gamepads
+            .iter()
+            .filter_map(|gamepad| self.gamepad_axes.get(GamepadAxis::new(gamepad, axis_type)))
+            .fold(0.0, |strongest: f32, value| {
+                if value.abs() > strongest.abs() {
+                    value
+                } else {
+                    strongest
+                }
+            })
+    }
+
+    fn gamepad_button(&self, button_type: GamepadButtonType) -> bool {
+        self.gamepads.iter().any(|gamepad| {
+            self.gamepad_buttons
+                .pressed(GamepadButton::new(gamepad, button_type))
+        })
+    }
+}
+
+pub fn read_actions(
+    mut raw: RawInputs,
+    bindings: Res<InputBindings>,
+    mut action_state: ResMut<ActionState>,
+) {
+    let mouse_motion = raw
+        .mouse_motion_events
+        .read()
+        .fold(Vec2::ZERO, |sum, event| sum + event.delta);
+    let mouse_wheel = raw
+        .mouse_wheel_events
+        .read()
+        .fold(0.0, |sum, event| sum + event.y);
+
+    for action in Action::ALL {
+        let was_pressed = action_state.pressed(action);
+        let mut value = ActionValue::default();
+        for binding in bindings.get(action) {
+            let raw_value = match binding.source {
+                InputSource::Key(key) => raw.keys.pressed(key) as u8 as f32,
+                InputSource::KeyPair { positive, negative } => {
+                    raw.keys.pressed(positive) as u8 as f32
+                        - raw.keys.pressed(negative) as u8 as f32
+                }
+                InputSource::MouseButton(button) => raw.mouse_buttons.pressed(button) as u8 as f32,
+                InputSource::MouseX => mouse_motion.x,
+                InputSource::MouseY => mouse_motion.y,
+                InputSource::MouseWheel => mouse_wheel,
+                InputSource::GamepadAxis(axis) => raw.gamepad_axis(axis),
+                InputSource::GamepadButton(button) => raw.gamepad_button(button) as u8 as f32,
+            };
+            if raw_value.abs() <= binding.deadzone || raw_value == 0.0 {
+                continue;
+            }
+            let signed = if binding.invert {
+                -raw_value
+            } else {
+                raw_value
+            };
+            if action.is_axis() {
+                if binding.source.is_relative() {
+                    value.delta += signed * binding.sensitivity;
+                } else {
+                    value.rate += signed * binding.sensitivity;
+                }
+            } else {
+                value.pressed |= signed > 0.0;
+            }
+        }
+        value.just_pressed = value.pressed && !was_pressed;
+        action_state.0.insert(action, value);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CaptureSlot {
+    Whole,
+    Positive,
+    Negative,
+}
+
+// Which binding is waiting for the player to press something.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Capture {
+    action: Action,
+    index: usize,
+    slot: CaptureSlot,
+}
+
+#[derive(Resource, Default)]
+pub struct RebindingPage {
+    capture: Option<Capture>,
+    status: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NewBinding {
+    Keys,
+    MouseButton,
+    MouseX,
+    MouseY,
+    MouseWheel,
+    GamepadAxis,
+    GamepadButton,
+}
+
+impl NewBinding {
+    const ALL: [NewBinding; 7] = [
+        NewBinding::Keys,
+        NewBinding::MouseButton,
+        NewBinding::MouseX,
+        NewBinding::MouseY,
+        NewBinding::MouseWheel,
+        NewBinding::GamepadAxis,
+        NewBinding::GamepadButton,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            NewBinding::Keys => "Keyboard",
+            NewBinding::MouseButton => "Mouse button",
+            NewBinding::MouseX => "Mouse left / right",
+            NewBinding::MouseY => "Mouse up / down",
+            NewBinding::MouseWheel => "Scroll wheel",
+            NewBinding::GamepadAxis => "Gamepad stick",
+            NewBinding::GamepadButton => "Gamepad button",
+        }
+    }
+
+    // A placeholder that the player then captures, or the finished binding for mouse axes.
+    fn source(&self, action: Action) -> (InputSource, bool) {
+        match self {
+            NewBinding::Keys if action.is_axis() => (
+                InputSource::KeyPair {
+                    positive: KeyCode::Unlabeled,
+                    negative: KeyCode::Unlabeled,
+                },
+                true,
+            ),
+            NewBinding::Keys => (InputSource::Key(KeyCode::Unlabeled), true),
+            NewBinding::MouseButton => (InputSource::MouseButton(MouseButton::Left), true),
+            NewBinding::MouseX => (InputSource::MouseX, false),
+            NewBinding::MouseY => (InputSource::MouseY, false),
+            NewBinding::MouseWheel => (InputSource::MouseWheel, false),
+            NewBinding::GamepadAxis => {
+                (InputSource::GamepadAxis(GamepadAxisType::LeftStickX), true)
+            }
+            NewBinding::GamepadButton => {
+                (InputSource::GamepadButton(GamepadButtonType::South), true)
+            }
+        }
+    }
+}
+
+/// Waits for the player to press whatever the binding being captured should become.
+/// Runs before `rebinding_page`, so the click that starts a capture is not itself captured.
+pub fn capture_binding(
+    raw: RawInputs,
+    mut page: ResMut<RebindingPage>,
+    mut bindings: ResMut<InputBindings>,
+) {
+    let Some(capture) = page.capture else {
+        return;
+    };
+    let Some(binding) = bindings
+        .bindings
+        .get_mut(&capture.action)
+        .and_then(|bindings| bindings.get_mut(capture.index))
+    else {
+        page.capture = None;
+        return;
+    };
+    let key = raw.keys.get_just_pressed().next().copied();
+    let gamepad_button = raw
+        .gamepad_buttons
+        .get_just_pressed()
+        .next()
+        .map(|button| button.button_type);
+    let gamepad_axis = [
+        GamepadAxisType::LeftStickX,
+        GamepadAxisType::LeftStickY,
+        GamepadAxisType::RightStickX,
+        GamepadAxisType::RightStickY,
+        GamepadAxisType::LeftZ,
+        GamepadAxisType::RightZ,
+    ]
+    .into_iter()
+    .find(|axis| raw.gamepad_axis(*axis).abs() > CAPTURE_THRESHOLD);
+
+    let mut next_slot = None;
+    match (&mut binding.source, capture.slot) {
+        (InputSource::Key(current), _) => {
+            let Some(key) = key else { return };
+            *current = key;
+        }
+        (InputSource::KeyPair { positive, .. }, CaptureSlot::Positive) => {
+            let Some(key) = key else { return };
+            *positive = key;
+            next_slot = Some(CaptureSlot::Negative);
+        }
+        (InputSource::KeyPair { negative, .. }, _) => {
+            let Some(key) = key else { return };
+            *negative = key;
+        }
+        (InputSource::MouseButton(current), _) => {
+            let Some(button) = raw.mouse_buttons.get_just_pressed().next() else {
+                return;
+            };
+            *current = *button;
+        }
+        (InputSource::GamepadAxis(current), _) => {
+            let Some(axis) = gamepad_axis else { return };
+            *current = axis;
+        }
+        (InputSource::GamepadButton(current), _) => {
+            let Some(button) = gamepad_button else { return };
+            *current = button;
+        }
+        (InputSource::MouseX | InputSource::MouseY | InputSource::MouseWheel, _) => {}
+    }
+    page.status = format!("{:?} bound to {}", capture.action, binding.source);
+    page.capture = next_slot.map(|slot| Capture { slot, ..capture });
+}
+
+pub fn rebinding_page(
+    mut ctx: EguiContexts,
+    mut page: ResMut<RebindingPage>,
+    mut bindings: ResMut<InputBindings>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let page = &mut *page;
+    Window::new("Input Bindings")
+        .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx.ctx_mut(), |ui| {
+            // Save and Back stay on screen however many actions there are.
+            ScrollArea::vertical()
+                .max_height(ui.ctx().screen_rect().height() * 0.7)
+                .show(ui, |ui| {
+                    for action in Action::ALL {
+                        ui.heading(RichText::new(action.description()).color(Color32::GREEN));
+                        let action_bindings = bindings.bindings.entry(action).or_default();
+                        let mut removed = None;
+                        Grid::new(action).striped(true).show(ui, |ui| {
+                            for (index, binding) in action_bindings.iter_mut().enumerate() {
+                                capture_buttons(ui, page, action, index, binding);
+                                if action.is_axis() {
+                                    let speed = binding.sensitivity.abs().max(0.001) * 0.05;
+                                    ui.add(
+                                        DragValue::new(&mut binding.sensitivity)
+                                            .speed(speed)
+                                            .prefix("sensitivity "),
+                                    );
+                                    ui.checkbox(&mut binding.invert, "invert");
+                                }
+                                if matches!(binding.source, InputSource::GamepadAxis(_)) {
+                                    ui.add(
+                                        DragValue::new(&mut binding.deadzone)
+                                            .speed(0.01)
+                                            .clamp_range(0.0..=0.95)
+                                            .prefix("deadzone "),
+                                    );
+                                }
+                                if ui.button("Remove").clicked() {
+                                    removed = Some(index);
+                                }
+                                ui.end_row();
+                            }
+                        });
+                        if let Some(index) = removed {
+                            action_bindings.remove(index);
+                            page.capture = None;
+                        }
+                        ComboBox::from_id_source((action, "add"))
+                            .selected_text("Add binding...")
+                            .show_ui(ui, |ui| {
+                                for new_binding in NewBinding::ALL {
+                                    if ui.selectable_label(false, new_binding.label()).clicked() {
+                                        let (source, needs_capture) = new_binding.source(action);
+                                        action_bindings.push(Binding::new(
+                                            source,
+                                            Binding::default_sensitivity(action, &source),
+                                        ));
+                                        let index = action_bindings.len() - 1;
+                                        page.capture = needs_capture.then(|| Capture {
+                                            action,
+                                            index,
+                                            slot: first_slot(&source),
+                                        });
+                                    }
+                                }
+                            });
+                        ui.separator();
+                    }
+                });
+            ui.label(RichText::new(&page.status).color(Color32::GREEN));
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    page.status = match bindings.save() {
+                        Ok(()) => format!("Saved to {:?}", InputBindings::path()),
+                        Err(error) => format!("Could not save: {error}"),
+                    };
+                }
+                if ui.button("Reset to defaults").clicked() {
+                    *bindings = InputBindings::default();
+                    page.capture = None;
+                }
+                if ui.button("Back").clicked() {
+                    page.capture = None;
+                    next_state.set(AppState::Help);
+                }
+            });
+        });
+}
+
+fn first_slot(source: &InputSource) -> CaptureSlot {
+    match source {
+        InputSource::KeyPair { .. } => CaptureSlot::Positive,
+        _ => CaptureSlot::Whole,
+    }
+}
+
+// One button per capturable part of the binding. Click it, then press the new input.
+fn capture_buttons(
+    ui: &mut egui::Ui,
+    page: &mut RebindingPage,
+    action: Action,
+    index: usize,
+    binding: &Binding,
+) {
+    let parts = match binding.source {
+        InputSource::KeyPair { positive, negative } => vec![
+            (CaptureSlot::Positive, format!("{positive:?}")),
+            (CaptureSlot::Negative, format!("{negative:?}")),
+        ],
+        InputSource::MouseX | InputSource::MouseY | InputSource::MouseWheel => {
+            ui.label(binding.source.to_string());
+            return;
+        }
+        source => vec![(CaptureSlot::Whole, source.to_string())],
+    };
+    ui.horizontal(|ui| {
+        for (slot, text) in parts {
+            let capture = Capture {
+                action,
+                index,
+                slot,
+            };
+            let text = if page.capture == Some(capture) {
+                "press...".to_string()
+            } else {
+                text
+            };
+            if ui.button(text).clicked() {
+                page.capture = Some(capture);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_file_keeps_defaults_for_the_rest() {
+        let defaults = InputBindings::default();
+        let mut bindings = InputBindings::default();
+        bindings.parse(
+            "# An old file, from before most actions existed\n\
+             fire key Return\n\
+             fire mouse_button Right\n\
+             pitch keys I K sensitivity=0.5 invert\n",
+        );
+
+        assert_eq!(
+            bindings.get(Action::Fire),
+            [
+                Binding::new(InputSource::Key(KeyCode::Return), 1.0),
+                Binding::new(InputSource::MouseButton(MouseButton::Right), 1.0),
+            ]
+        );
+        assert_eq!(
+            bindings.get(Action::Pitch),
+            [Binding::new(
+                InputSource::KeyPair {
+                    positive: KeyCode::I,
+                    negative: KeyCode::K,
+                },
+                0.5,
+            )
+            .inverted()]
+        );
+        for action in Action::ALL {
+            if !matches!(action, Action::Fire | Action::Pitch) {
+                assert_eq!(bindings.get(action), defaults.get(action), "{action:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn saved_bindings_parse_back() {
+        let mut saved = InputBindings::default();
+        saved.bindings.insert(Action::Rewind, Vec::new());
+        saved.bindings.insert(
+            Action::Replay,
+            vec![Binding::new(
+                InputSource::GamepadButton(GamepadButtonType::Start),
+                1.0,
+            )],
+        );
+
+        let mut bindings = InputBindings::default();
+        bindings.parse(&saved.to_string());
+        assert_eq!(bindings, saved);
+        assert!(bindings.get(Action::Rewind).is_empty());
+    }
+}
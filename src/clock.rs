@@ -0,0 +1,188 @@
+use bevy::prelude::*;
+use std::fmt;
+use std::time::Duration;
+
+use crate::bindings::{Action, ActionState};
+
+/// The fastest the simulation runs, as a multiple of real time.
+pub const MAX_SCALE: f32 = 16.0;
+/// The slowest it runs without stopping altogether.
+pub const MIN_SCALE: f32 = 1.0 / 16.0;
+/// How far a single step moves a stopped simulation.
+pub const STEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// Time as the planets and projectiles see it: gravity, homing, lifetimes and animations all run
+/// on this, so they can be slowed down, sped up or stopped together. The spacecraft, camera and
+/// everything else the player does run on bevy's `Time`, so you can fly around a frozen collision.
+#[derive(Resource, Debug)]
+pub struct SimulationClock {
+    /// A multiple of real time, from 0.0 (stopped) to `MAX_SCALE`.
+    pub scale: f32,
+    pub paused: bool,
+    step: bool,
+    delta: Duration,
+    elapsed: Duration,
+}
+
+impl Default for SimulationClock {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            paused: false,
+            step: false,
+            delta: Duration::ZERO,
+            elapsed: Duration::ZERO,
+        }
+    }
+}
+
+impl SimulationClock {
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Wind the clock back (or on) to match a rewound simulation.
+    pub fn set_elapsed(&mut self, elapsed: Duration) {
+        self.elapsed = elapsed;
+    }
+
+    pub fn elapsed_seconds(&self) -> f32 {
+        self.elapsed.as_secs_f32()
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.paused || self.scale <= 0.0
+    }
+
+    /// Move a stopped simulation on by one `STEP`, next frame.
+    pub fn step(&mut self) {
+        self.step = true;
+    }
+
+    pub fn faster(&mut self) {
+        self.scale = (self.scale * 2.0).clamp(MIN_SCALE, MAX_SCALE);
+    }
+
+    pub fn slower(&mut self) {
+        self.scale = (self.scale / 2.0).clamp(MIN_SCALE, MAX_SCALE);
+    }
+
+    fn tick(&mut self, real_delta: Duration) {
+        self.delta = if self.is_stopped() {
+            if std::mem::take(&mut self.step) {
+                STEP
+            } else {
+                Duration::ZERO
+            }
+        } else {
+            self.step = false;
+            real_delta.mul_f32(self.scale.min(MAX_SCALE))
+        };
+        self.elapsed += self.delta;
+    }
+}
+
+impl fmt::Display for SimulationClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_stopped() {
+            write!(f, "paused")
+        } else if self.scale < 1.0 {
+            write!(f, "1/{:.0}x", 1.0 / self.scale)
+        } else {
+            write!(f, "{:.0}x", self.scale)
+        }
+    }
+}
+
+/// Runs before `Update`, so every system there sees the same tick. Help and the other menus
+/// pause bevy's virtual time, which stops this too.
+pub fn tick_simulation_clock(time: Res<Time>, mut clock: ResMut<SimulationClock>) {
+    clock.tick(time.delta());
+}
+
+pub fn control_clock(actions: Res<ActionState>, mut clock: ResMut<SimulationClock>) {
+    if actions.just_pressed(Action::SlowDown) {
+        clock.slower();
+    }
+    if actions.just_pressed(Action::SpeedUp) {
+        clock.faster();
+    }
+    if actions.just_pressed(Action::PauseTime) {
+        clock.paused = !clock.paused;
+    }
+    if actions.just_pressed(Action::StepTime) {
+        clock.paused = true;
+        clock.step();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(10);
+
+    #[test]
+    fn a_step_while_paused_moves_exactly_one_step() {
+        let mut clock = SimulationClock {
+            paused: true,
+            ..default()
+        };
+        clock.tick(FRAME);
+        assert_eq!(clock.delta(), Duration::ZERO);
+
+        clock.step();
+        clock.tick(FRAME);
+        assert_eq!(clock.delta(), STEP);
+        clock.tick(FRAME);
+        assert_eq!(clock.delta(), Duration::ZERO);
+        assert_eq!(clock.elapsed(), STEP);
+    }
+
+    #[test]
+    fn a_step_is_dropped_while_running() {
+        let mut clock = SimulationClock::default();
+        clock.step();
+        clock.tick(FRAME);
+        assert_eq!(clock.delta(), FRAME);
+
+        // It mustn't be saved up for the next pause.
+        clock.paused = true;
+        clock.tick(FRAME);
+        assert_eq!(clock.delta(), Duration::ZERO);
+    }
+
+    #[test]
+    fn scale_stays_within_bounds() {
+        let mut clock = SimulationClock::default();
+        for _ in 0..10 {
+            clock.faster();
+        }
+        assert_eq!(clock.scale, MAX_SCALE);
+        for _ in 0..20 {
+            clock.slower();
+        }
+        assert_eq!(clock.scale, MIN_SCALE);
+        assert!(!clock.is_stopped());
+    }
+
+    #[test]
+    fn elapsed_adds_up_scaled_deltas() {
+        let mut clock = SimulationClock::default();
+        clock.tick(FRAME);
+        clock.faster();
+        clock.tick(FRAME);
+        clock.paused = true;
+        clock.tick(FRAME);
+        assert_eq!(clock.delta(), Duration::ZERO);
+        assert_eq!(clock.elapsed(), FRAME * 3);
+    }
+}
@@ -0,0 +1,35 @@
+// Red/cyan anaglyph. The left eye is seen through the red filter and the right eye through the
+// cyan one. Red carries the left eye's brightness rather than its red channel ("half color"), so
+// red things don't vanish from one eye.
+
+@group(1) @binding(0) var left_texture: texture_2d<f32>;
+@group(1) @binding(1) var left_sampler: sampler;
+@group(1) @binding(2) var right_texture: texture_2d<f32>;
+@group(1) @binding(3) var right_sampler: sampler;
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// The quad's corners are already in clip space, so it covers the screen whatever its size.
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = vec4<f32>(vertex.position.xy, 0.0, 1.0);
+    out.uv = vertex.uv;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let left = textureSample(left_texture, left_sampler, in.uv);
+    let right = textureSample(right_texture, right_sampler, in.uv);
+    let brightness = dot(left.rgb, vec3<f32>(0.299, 0.587, 0.114));
+    return vec4<f32>(brightness, right.g, right.b, 1.0);
+}
@@ -0,0 +1,108 @@
+use bevy::prelude::*;
+
+use super::{HotPlanetEvent, Spacecraft, SpacecraftConfig};
+use crate::bindings::{Action, ActionState};
+use crate::mass_to_radius;
+use crate::physics::{Momentum, GRAVITATIONAL_CONSTANT};
+
+// Seconds the autopilot allows itself to close a velocity (or heading) error.
+const RESPONSE_TIME: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AutopilotMode {
+    /// Come to rest relative to the target.
+    MatchVelocity,
+    /// Close to `SpacecraftConfig::approach_distance` from the target's surface, then hold.
+    Approach,
+    /// Circle the target at the current distance, as fast as its gravity would allow.
+    Orbit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Autopilot {
+    pub planet: Entity,
+    pub mode: AutopilotMode,
+}
+
+/// Hand the controls to the autopilot, targeting whatever is under the crosshairs.
+pub fn engage_autopilot(
+    actions: Res<ActionState>,
+    mut spacecraft_query: Query<&mut Spacecraft>,
+    mut hot_planet_events: EventReader<HotPlanetEvent>,
+) {
+    let Some(planet) = hot_planet_events.read().last().map(|event| event.planet) else {
+        return;
+    };
+    let mode = if actions.just_pressed(Action::MatchVelocity) {
+        AutopilotMode::MatchVelocity
+    } else if actions.just_pressed(Action::Approach) {
+        AutopilotMode::Approach
+    } else if actions.just_pressed(Action::Orbit) {
+        AutopilotMode::Orbit
+    } else {
+        return;
+    };
+    for mut spacecraft in spacecraft_query.iter_mut() {
+        debug!("Autopilot engaged: {mode:?} with planet {planet:?}");
+        spacecraft.autopilot = Some(Autopilot { planet, mode });
+        spacecraft.throttle = 0.0;
+    }
+}
+
+/// Overrides what `control` asked of the thrusters and reaction wheels.
+pub fn autopilot(
+    mut spacecraft_query: Query<(&Transform, &mut Spacecraft)>,
+    planet_query: Query<(&Transform, &Momentum)>,
+    config: Res<SpacecraftConfig>,
+) {
+    for (transform, mut spacecraft) in spacecraft_query.iter_mut() {
+        let Some(Autopilot { planet, mode }) = spacecraft.autopilot else {
+            continue;
+        };
+        let Ok((planet_transform, momentum)) = planet_query.get(planet) else {
+            debug!("Autopilot disengaged: planet {planet:?} is gone");
+            spacecraft.autopilot = None;
+            continue;
+        };
+        let offset = transform.translation - planet_transform.translation;
+        let distance = offset.length();
+        let outward = offset.normalize_or_zero();
+        let relative_velocity = spacecraft.velocity - momentum.velocity;
+
+        // The spacecraft does not fall, so any orbit is held up by the thrusters.
+        let mut feed_forward = Vec3::ZERO;
+        let target_velocity = match mode {
+            AutopilotMode::MatchVelocity => Vec3::ZERO,
+            AutopilotMode::Approach => {
+                let error = distance - mass_to_radius(momentum.mass) - config.approach_distance;
+                // As fast as we can go and still stop in time.
+                let speed = (config.thrust * error.abs())
+                    .sqrt()
+                    .min(config.autopilot_speed);
+                -outward * speed * error.signum()
+            }
+            AutopilotMode::Orbit => {
+                let tangent = relative_velocity.reject_from(outward);
+                let tangent = if tangent.length() > 0.01 {
+                    tangent.normalize()
+                } else {
+                    transform.right().reject_from(outward).normalize_or_zero()
+                };
+                let speed = (GRAVITATIONAL_CONSTANT * momentum.mass / distance).sqrt();
+                feed_forward = -outward * speed * speed / distance;
+                tangent * speed
+            }
+        };
+        let acceleration = (target_velocity - relative_velocity) / RESPONSE_TIME + feed_forward;
+        spacecraft.thrust = (transform.rotation.inverse() * acceleration / config.thrust)
+            .clamp(Vec3::splat(-1.0), Vec3::splat(1.0));
+
+        // Keep the target in the crosshairs.
+        if outward != Vec3::ZERO {
+            let local_direction = transform.rotation.inverse() * -outward;
+            let (axis, angle) =
+                Quat::from_rotation_arc(Vec3::NEG_Z, local_direction).to_axis_angle();
+            spacecraft.turn = axis * angle / RESPONSE_TIME;
+        }
+    }
+}
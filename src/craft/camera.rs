@@ -0,0 +1,270 @@
+use bevy::{core_pipeline::Skybox, prelude::*};
+use std::fmt;
+
+use super::{LeftCamera, LockedTarget, RightCamera, Spacecraft, SpacecraftConfig};
+use crate::bindings::{Action, ActionState};
+use crate::mass_to_radius;
+use crate::physics::Momentum;
+use crate::sky::{skybox_image, SkyConfig};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    /// First person, from the pilot's seat.
+    #[default]
+    Cockpit,
+    /// Third person, trailing behind the spacecraft.
+    Chase,
+    /// Circling the locked planet (or the spacecraft, with nothing locked).
+    OrbitTarget,
+    /// Looking straight down on the whole system.
+    SystemMap,
+    /// Detached from the spacecraft and flown with the flight controls, for spectating.
+    Cinematic,
+}
+
+impl CameraMode {
+    pub fn next(&self) -> Self {
+        match self {
+            CameraMode::Cockpit => CameraMode::Chase,
+            CameraMode::Chase => CameraMode::OrbitTarget,
+            CameraMode::OrbitTarget => CameraMode::SystemMap,
+            CameraMode::SystemMap => CameraMode::Cinematic,
+            CameraMode::Cinematic => CameraMode::Cockpit,
+        }
+    }
+}
+
+impl fmt::Display for CameraMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CameraMode::Cockpit => write!(f, "Cockpit"),
+            CameraMode::Chase => write!(f, "Chase"),
+            CameraMode::OrbitTarget => write!(f, "Orbit"),
+            CameraMode::SystemMap => write!(f, "System map"),
+            CameraMode::Cinematic => write!(f, "Cinematic"),
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct CameraConfig {
+    /// Where the chase camera sits, relative to the spacecraft.
+    pub chase_offset: Vec3,
+    /// How quickly the chase camera catches up. Higher is stiffer.
+    pub chase_stiffness: f32,
+    /// Radians per second.
+    pub orbit_speed: f32,
+    /// Orbit this many planet radii out, plus `orbit_margin`.
+    pub orbit_radii: f32,
+    pub orbit_margin: f32,
+    pub map_height: f32,
+    pub cinematic_speed: f32,
+    /// Seconds to blend from one mode to the next.
+    pub transition: f32,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        Self {
+            chase_offset: Vec3::new(0.0, 1.5, 6.0),
+            chase_stiffness: 4.0,
+            orbit_speed: 0.2,
+            orbit_radii: 3.0,
+            orbit_margin: 10.0,
+            map_height: 150.0,
+            cinematic_speed: 20.0,
+            transition: 0.75,
+        }
+    }
+}
+
+/// Carries the cameras, one for each eye. It is not a child of the spacecraft, so it can leave it.
+#[derive(Component)]
+pub struct CameraRig {
+    pub mode: CameraMode,
+    // Where the last transition started, and how far along it is (0.0 to 1.0).
+    from: Transform,
+    blend: f32,
+    // Lagging and free-flying poses are kept up to date in every mode, so switching is seamless.
+    chase: Transform,
+    cinematic: Transform,
+    orbit_angle: f32,
+}
+
+impl CameraRig {
+    fn new(start: Transform) -> Self {
+        Self {
+            mode: CameraMode::default(),
+            from: start,
+            blend: 1.0,
+            chase: start,
+            cinematic: start,
+            orbit_angle: 0.0,
+        }
+    }
+}
+
+/// Something only seen from outside, like the spacecraft's hull.
+#[derive(Component)]
+pub struct SpacecraftHull;
+
+pub fn camera_rig_setup(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    config: Res<SpacecraftConfig>,
+    sky: Res<SkyConfig>,
+) {
+    let skybox = images.add(skybox_image(&sky));
+    commands
+        .spawn(TransformBundle::from_transform(config.start_transform))
+        .insert(VisibilityBundle::default())
+        .insert(CameraRig::new(config.start_transform))
+        .with_children(|child| {
+            // The right eye only opens in stereo. `apply_stereo` places both.
+            child
+                .spawn(Camera3dBundle::default())
+                .insert(Skybox(skybox.clone()))
+                .insert(LeftCamera);
+            child
+                .spawn(Camera3dBundle {
+                    camera: Camera {
+                        order: 1,
+                        is_active: false,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .insert(Skybox(skybox))
+                .insert(RightCamera);
+        });
+}
+
+pub fn switch_camera_mode(
+    actions: Res<ActionState>,
+    mut rig_query: Query<(&Transform, &mut CameraRig)>,
+) {
+    if !actions.just_pressed(Action::CameraMode) {
+        return;
+    }
+    for (transform, mut rig) in rig_query.iter_mut() {
+        rig.mode = rig.mode.next();
+        rig.from = *transform;
+        rig.blend = 0.0;
+        if rig.mode == CameraMode::Cinematic {
+            rig.cinematic = *transform;
+        }
+        debug!("Camera mode: {}", rig.mode);
+    }
+}
+
+/// The flight controls steer the cinematic camera instead of the spacecraft.
+pub fn fly_cinematic_camera(
+    actions: Res<ActionState>,
+    mut rig_query: Query<&mut CameraRig>,
+    config: Res<CameraConfig>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+    for mut rig in rig_query.iter_mut() {
+        if rig.mode != CameraMode::Cinematic {
+            continue;
+        }
+        let [pitch, yaw, roll] = [Action::Pitch, Action::Yaw, Action::Roll].map(|action| {
+            let value = actions.get(action);
+            value.rate * dt + value.delta
+        });
+        let pose = &mut rig.cinematic;
+        pose.rotate_local(Quat::from_euler(EulerRot::XYZ, pitch, yaw, roll));
+        let movement = Vec3::new(
+            actions.get(Action::Strafe).rate,
+            actions.get(Action::Lift).rate,
+            -actions.get(Action::Throttle).rate,
+        )
+        .clamp(Vec3::splat(-1.0), Vec3::splat(1.0));
+        pose.translation += pose.rotation * movement * config.cinematic_speed * dt;
+    }
+}
+
+pub fn move_camera_rig(
+    mut rig_query: Query<(&mut Transform, &mut CameraRig), Without<Spacecraft>>,
+    spacecraft_query: Query<&Transform, With<Spacecraft>>,
+    planet_query: Query<(&Transform, &Momentum), Without<CameraRig>>,
+    locked: Res<LockedTarget>,
+    config: Res<CameraConfig>,
+    time: Res<Time>,
+) {
+    let Ok(spacecraft) = spacecraft_query.get_single() else {
+        return;
+    };
+    let dt = time.delta_seconds();
+    for (mut transform, mut rig) in rig_query.iter_mut() {
+        let desired_chase = Transform {
+            translation: spacecraft.translation + spacecraft.rotation * config.chase_offset,
+            ..*spacecraft
+        };
+        let catch_up = 1.0 - (-config.chase_stiffness * dt).exp();
+        rig.chase = lerp_transform(&rig.chase, &desired_chase, catch_up);
+        rig.orbit_angle += config.orbit_speed * dt;
+
+        let target = match rig.mode {
+            CameraMode::Cockpit => *spacecraft,
+            CameraMode::Chase => rig.chase,
+            CameraMode::OrbitTarget => {
+                let (center, radius) = locked
+                    .planet
+                    .and_then(|planet| planet_query.get(planet).ok())
+                    .map(|(planet, momentum)| (planet.translation, mass_to_radius(momentum.mass)))
+                    .unwrap_or((spacecraft.translation, 0.5));
+                let distance = radius * config.orbit_radii + config.orbit_margin;
+                let (sin, cos) = rig.orbit_angle.sin_cos();
+                Transform::from_translation(
+                    center + Vec3::new(cos * distance, distance * 0.3, sin * distance),
+                )
+                .looking_at(center, Vec3::Y)
+            }
+            CameraMode::SystemMap => {
+                let count = planet_query.iter().count().max(1) as f32;
+                let center = planet_query
+                    .iter()
+                    .fold(Vec3::ZERO, |sum, (planet, _)| sum + planet.translation)
+                    / count;
+                Transform::from_translation(center + Vec3::Y * config.map_height)
+                    .looking_at(center, -Vec3::Z)
+            }
+            CameraMode::Cinematic => rig.cinematic,
+        };
+
+        if rig.blend < 1.0 {
+            rig.blend = (rig.blend + dt / config.transition).min(1.0);
+            // Ease in and out.
+            let t = rig.blend * rig.blend * (3.0 - 2.0 * rig.blend);
+            *transform = lerp_transform(&rig.from, &target, t);
+        } else {
+            *transform = target;
+        }
+    }
+}
+
+pub fn show_hull(
+    rig_query: Query<&CameraRig>,
+    mut hull_query: Query<&mut Visibility, With<SpacecraftHull>>,
+) {
+    let outside = rig_query
+        .iter()
+        .any(|rig| rig.mode != CameraMode::Cockpit || rig.blend < 1.0);
+    for mut visibility in hull_query.iter_mut() {
+        *visibility = if outside {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+fn lerp_transform(from: &Transform, to: &Transform, t: f32) -> Transform {
+    Transform {
+        translation: from.translation.lerp(to.translation, t),
+        rotation: from.rotation.slerp(to.rotation, t),
+        scale: from.scale.lerp(to.scale, t),
+    }
+}
@@ -1,101 +1,78 @@
-use bevy::input::mouse::{MouseButton, MouseButtonInput, MouseMotion, MouseWheel};
-use bevy::input::ButtonState;
-use bevy::prelude::{
-    EventReader, EventWriter, Input, KeyCode, Quat, Query, Res, Time, Transform, Vec3,
-};
-use std::f32::consts::TAU;
+use bevy::prelude::{debug, EventWriter, Query, Res, Time, Vec3};
 
 use super::FireProjectileEvent;
-use super::Spacecraft;
+use super::{CameraMode, CameraRig, Spacecraft};
+use crate::bindings::{Action, ActionState};
 
 pub fn control(
-    keys: Res<Input<KeyCode>>,
-    mut spacecraft_query: Query<(&mut Transform, &mut Spacecraft)>,
-    mut mouse_motion_events: EventReader<MouseMotion>,
-    mut mouse_button_input_events: EventReader<MouseButtonInput>,
-    mut mouse_wheel_events: EventReader<MouseWheel>,
+    actions: Res<ActionState>,
+    mut spacecraft_query: Query<&mut Spacecraft>,
     mut fire_projectile_events: EventWriter<FireProjectileEvent>,
+    rig_query: Query<&CameraRig>,
     time: Res<Time>,
 ) {
-    // FIXME: this does not regard time delta, which it should.
-    let nudge = TAU / 10000.0;
-
-    let keys_scaling = 10.0;
-    let mouse_scaling = 0.0001;
-
-    // rotation about local axes
-    let mut rotation = Vec3::ZERO;
+    let dt = time.delta_seconds();
+    if dt == 0.0 {
+        return;
+    }
 
-    let (mut transform, mut spacecraft) = spacecraft_query
+    let mut spacecraft = spacecraft_query
         .get_single_mut()
         .expect("Spacecraft query failed");
 
-    // `just_presssed` ignores keys held down.
-    for key in keys.get_just_pressed() {
-        match key {
-            KeyCode::PageUp => {
-                spacecraft.speed += 1.0 + spacecraft.speed * 0.05;
-            }
-            KeyCode::PageDown => {
-                spacecraft.speed -= 1.0 + spacecraft.speed * 0.05;
-            }
-            _ => {}
-        }
+    // Spectating: the flight controls fly the camera, and the spacecraft holds its course.
+    if rig_query
+        .iter()
+        .any(|rig| rig.mode == CameraMode::Cinematic)
+    {
+        spacecraft.thrust = Vec3::new(0.0, 0.0, -spacecraft.throttle);
+        spacecraft.turn = Vec3::ZERO;
+        return;
     }
 
+    let throttle = actions.get(Action::Throttle);
+    spacecraft.throttle =
+        (spacecraft.throttle + throttle.rate * dt + throttle.delta).clamp(-1.0, 1.0);
     // Make it easier to find "neutral"
-    if spacecraft.speed.abs() < 0.5 {
-        spacecraft.speed = 0.0
+    if !throttle.is_active() && spacecraft.throttle.abs() < 0.05 {
+        spacecraft.throttle = 0.0
     }
 
-    // `presssed` (contrast `just_pressed`) considers keys being _held_ down, which is good for rotation controls.
-    for key in keys.get_pressed() {
-        match key {
-            KeyCode::A => {
-                rotation.y += nudge;
-            }
-            KeyCode::D => {
-                rotation.y -= nudge;
-            }
-            KeyCode::W => {
-                rotation.x += nudge;
-            }
-            KeyCode::S => {
-                rotation.x -= nudge;
-            }
-            KeyCode::Z => {
-                rotation.z += nudge;
-            }
-            KeyCode::X => {
-                rotation.z -= nudge;
-            }
-            KeyCode::Space => {
-                fire_projectile_events.send(FireProjectileEvent);
-            }
-            _ => (),
-        }
+    if actions.just_pressed(Action::FlightAssist) {
+        spacecraft.flight_assist = !spacecraft.flight_assist;
+        debug!("Flight assist: {}", spacecraft.flight_assist);
     }
 
-    for event in mouse_motion_events.read() {
-        rotation.x -= event.delta.y * mouse_scaling;
-        rotation.y -= event.delta.x * mouse_scaling;
+    // Any hand on the flight controls takes them back from the autopilot.
+    let manual = [
+        Action::Pitch,
+        Action::Yaw,
+        Action::Roll,
+        Action::Throttle,
+        Action::Strafe,
+        Action::Lift,
+    ]
+    .into_iter()
+    .any(|action| actions.get(action).is_active());
+    if manual && spacecraft.autopilot.take().is_some() {
+        debug!("Autopilot disengaged");
     }
-    for MouseButtonInput { button, state, .. } in mouse_button_input_events.read() {
-        if button == &MouseButton::Left && state == &ButtonState::Pressed {
-            fire_projectile_events.send(FireProjectileEvent);
-        }
-    }
-    for event in mouse_wheel_events.read() {
-        spacecraft.speed = event.y;
+
+    if actions.pressed(Action::Fire) {
+        fire_projectile_events.send(FireProjectileEvent);
     }
 
-    let frame_time = time.delta_seconds() * 60.0;
-    rotation *= keys_scaling * frame_time;
+    // Forward is -Z.
+    spacecraft.thrust = Vec3::new(
+        actions.get(Action::Strafe).rate,
+        actions.get(Action::Lift).rate,
+        -spacecraft.throttle,
+    );
 
-    let local_x = transform.local_x();
-    let local_y = transform.local_y();
-    let local_z = transform.local_z();
-    transform.rotate(Quat::from_axis_angle(local_x, rotation.x));
-    transform.rotate(Quat::from_axis_angle(local_z, rotation.z));
-    transform.rotate(Quat::from_axis_angle(local_y, rotation.y));
+    // Held inputs ask for a rate of turn. Mouse movement asks to have turned that far this frame.
+    let [pitch, yaw, roll] = [Action::Pitch, Action::Yaw, Action::Roll].map(|action| {
+        let value = actions.get(action);
+        value.rate + value.delta / dt
+    });
+    spacecraft.turn = Vec3::new(pitch, yaw, roll);
 }
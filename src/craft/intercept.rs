@@ -0,0 +1,172 @@
+use bevy::prelude::*;
+
+use super::{HotPlanetEvent, Spacecraft, Weapon};
+use crate::mass_to_radius;
+use crate::physics::Momentum;
+
+// Homing projectiles fly faster the further they have to go: `speed * (d + 30) / (d + 1)`.
+const HOMING_BOOST: f32 = 30.0;
+
+/// Speed (relative to the target planet) of a homing projectile `distance` from its target.
+pub fn homing_speed(muzzle_speed: f32, distance: f32) -> f32 {
+    muzzle_speed * ((distance + HOMING_BOOST) / (distance + 1.0))
+}
+
+/// How long a homing projectile takes to cover `distance`. This is the integral of
+/// `1 / homing_speed` from zero to `distance`.
+pub fn homing_flight_time(muzzle_speed: f32, distance: f32) -> f32 {
+    let boost = HOMING_BOOST - 1.0;
+    (distance - boost * ((distance + HOMING_BOOST) / HOMING_BOOST).ln()) / muzzle_speed
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Intercept {
+    pub planet: Entity,
+    /// Seconds from launch to impact.
+    pub time: f32,
+    /// Where the impact site will be when the projectile gets there.
+    pub point: Vec3,
+}
+
+/// Solve for the impact of a homing shot launched from `launch` at the site `local_direction`
+/// on a planet. Homing cancels out the planet's velocity, so in the planet's frame the shot
+/// flies straight to the site. Gravity bends the planet's course a little in the meantime,
+/// which this ignores.
+pub fn solve_intercept(
+    launch: Vec3,
+    planet: Entity,
+    planet_transform: &Transform,
+    momentum: &Momentum,
+    local_direction: Vec3,
+    muzzle_speed: f32,
+) -> Intercept {
+    let site = planet_transform.translation + local_direction * mass_to_radius(momentum.mass);
+    let time = homing_flight_time(muzzle_speed, launch.distance(site));
+    Intercept {
+        planet,
+        time,
+        point: site + momentum.velocity * time,
+    }
+}
+
+/// Where and when a shot fired now would land, if it were fired at the hot planet.
+#[derive(Resource, Debug, Default)]
+pub struct InterceptPrediction(pub Option<Intercept>);
+
+#[derive(Component)]
+pub struct InterceptMarker;
+
+pub fn predict_intercept(
+    spacecraft_query: Query<(&Transform, &Weapon), With<Spacecraft>>,
+    planet_query: Query<(&Transform, &Momentum)>,
+    mut hot_planet_events: EventReader<HotPlanetEvent>,
+    mut prediction: ResMut<InterceptPrediction>,
+) {
+    prediction.0 = None;
+    let Ok((transform, weapon)) = spacecraft_query.get_single() else {
+        return;
+    };
+    if let Some(event) = hot_planet_events.read().last() {
+        if let Ok((planet_transform, momentum)) = planet_query.get(event.planet) {
+            prediction.0 = Some(solve_intercept(
+                transform.translation,
+                event.planet,
+                planet_transform,
+                momentum,
+                event.local_direction,
+                weapon.muzzle_speed,
+            ));
+        }
+    }
+}
+
+pub fn intercept_marker_setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands
+        .spawn(PbrBundle {
+            mesh: meshes.add(
+                Mesh::try_from(shape::Icosphere {
+                    radius: 0.15,
+                    ..default()
+                })
+                .unwrap(),
+            ),
+            material: materials.add(StandardMaterial {
+                emissive: Color::FUCHSIA * 5.0,
+                unlit: true,
+                ..default()
+            }),
+            visibility: Visibility::Hidden,
+            ..default()
+        })
+        .insert(InterceptMarker);
+}
+
+pub fn show_intercept_marker(
+    prediction: Res<InterceptPrediction>,
+    mut marker_query: Query<(&mut Transform, &mut Visibility), With<InterceptMarker>>,
+) {
+    for (mut transform, mut visibility) in marker_query.iter_mut() {
+        match prediction.0 {
+            Some(intercept) => {
+                transform.translation = intercept.point;
+                *visibility = Visibility::Visible;
+            }
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fly a homing shot for `time` seconds, and return how far it got.
+    fn fly(muzzle_speed: f32, distance: f32, time: f32) -> f32 {
+        let steps = 100_000;
+        let dt = time / steps as f32;
+        let mut remaining = distance;
+        for _ in 0..steps {
+            remaining -= homing_speed(muzzle_speed, remaining.max(0.0)) * dt;
+        }
+        distance - remaining
+    }
+
+    #[test]
+    fn flight_time_covers_the_distance() {
+        for (muzzle_speed, distance) in [(120.0, 5.0), (90.0, 40.0), (60.0, 300.0)] {
+            let time = homing_flight_time(muzzle_speed, distance);
+            let covered = fly(muzzle_speed, distance, time);
+            assert!(
+                (covered - distance).abs() < distance * 1e-3,
+                "{covered} of {distance} in {time}s"
+            );
+        }
+    }
+
+    #[test]
+    fn no_distance_takes_no_time() {
+        assert_eq!(homing_flight_time(120.0, 0.0), 0.0);
+
+        let planet = Transform::from_xyz(10.0, 0.0, 0.0);
+        let momentum = Momentum {
+            velocity: Vec3::Y * 3.0,
+            mass: 100.0,
+            ..default()
+        };
+        let site = planet.translation + Vec3::X * mass_to_radius(momentum.mass);
+        let intercept = solve_intercept(
+            site,
+            Entity::PLACEHOLDER,
+            &planet,
+            &momentum,
+            Vec3::X,
+            120.0,
+        );
+        assert_eq!(intercept.time, 0.0);
+        assert_eq!(intercept.point, site);
+    }
+}
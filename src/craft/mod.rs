@@ -1,5 +1,5 @@
+use bevy::prelude::*;
 use bevy::transform::TransformBundle;
-use bevy::{core_pipeline::clear_color::ClearColorConfig, prelude::*};
 
 use bevy_rapier3d::prelude::{
     ActiveEvents, Collider, QueryFilter, RapierContext, RigidBody, Sensor,
@@ -7,13 +7,25 @@ use bevy_rapier3d::prelude::{
 
 use rand::Rng;
 use std::collections::HashSet;
+use std::time::Duration;
 
+mod autopilot;
+mod camera;
 mod controls;
+mod intercept;
+mod stereo;
+mod targeting;
+mod weapons;
+pub use autopilot::*;
+pub use camera::*;
 pub use controls::*;
+pub use intercept::*;
+pub use stereo::*;
+pub use targeting::*;
+pub use weapons::*;
 
-use crate::{mass_to_radius, physics::Momentum, DeltaEvent};
-
-pub const SQRT_3: f32 = 1.7320508_f32;
+use crate::clock::SimulationClock;
+use crate::{mass_to_radius, physics::Momentum, DeltaEvent, DespawnTimer};
 
 #[derive(Component, PartialEq, Eq)]
 pub enum SpacecraftAR {
@@ -23,7 +35,30 @@ pub enum SpacecraftAR {
 
 #[derive(Debug, Default, Component)]
 pub struct Spacecraft {
-    pub speed: f32,
+    pub velocity: Vec3,
+    /// Radians per second about the spacecraft's local axes.
+    pub angular_velocity: Vec3,
+    /// Main engine setting, from full reverse (-1.0) to full ahead (1.0).
+    pub throttle: f32,
+    // What the controls ask of the thrusters (local, -1.0 to 1.0 per axis) and of the
+    // reaction wheels (local angular velocity) this frame. Zero means "no input".
+    pub thrust: Vec3,
+    pub turn: Vec3,
+    pub fuel: f32,
+    /// Thrusters cancel drift, and reaction wheels cancel spin, on axes with no input.
+    pub flight_assist: bool,
+    pub autopilot: Option<Autopilot>,
+}
+
+impl Spacecraft {
+    pub fn new(config: &SpacecraftConfig) -> Self {
+        Self {
+            velocity: config.start_transform.forward() * config.start_speed,
+            fuel: config.fuel_capacity,
+            flight_assist: config.flight_assist,
+            ..default()
+        }
+    }
 }
 
 #[derive(Component)]
@@ -35,34 +70,74 @@ pub struct RightCamera;
 #[derive(Resource)]
 pub struct SpacecraftConfig {
     pub show_debug_markers: bool,
-    pub show_impact_explosions: bool,
     pub projectile_radius: f32,
-    pub stereo_enabled: bool,
-    /// Hint: use a negative value for "crosseyed" mode.
+    pub stereo_mode: StereoMode,
     pub stereo_iod: f32, // interocular distance
+    /// How far ahead the eyes' lines of sight cross.
+    pub stereo_convergence: f32,
     pub recoil: f32,
+    /// Multiplies the impulse of every weapon. 1.0 leaves them as they are. This replaces
+    /// `impact_magnitude`, which was the impulse itself: 5.0 then is 1.0 now.
+    pub impact_scale: f32,
+    /// How long a projectile with no target flies before it burns out.
+    pub projectile_lifetime: Duration,
     pub start_transform: Transform,
-    pub impact_magnitude: f32,
     pub start_speed: f32,
+    /// Acceleration from one thruster at full burn.
+    pub thrust: f32,
+    /// Angular acceleration the reaction wheels can manage about each axis.
+    pub angular_acceleration: f32,
+    // Fraction of spin lost per second, even with flight assist off.
+    pub angular_damping: f32,
+    pub fuel_capacity: f32,
+    /// Fuel burned per second by one thruster at full burn.
+    pub fuel_burn: f32,
+    /// Fuel regained per second. Zero makes fuel a hard budget.
+    pub fuel_recharge: f32,
+    /// Whether flight assist starts out on.
+    pub flight_assist: bool,
+    /// How far from the target's surface the approach autopilot stops.
+    pub approach_distance: f32,
+    /// The fastest the autopilot will close on its target.
+    pub autopilot_speed: f32,
 }
 
 impl Default for SpacecraftConfig {
     fn default() -> Self {
         Self {
             show_debug_markers: false,
-            show_impact_explosions: true,
             projectile_radius: 0.1,
-            stereo_enabled: false,
-            stereo_iod: 0.0,
+            stereo_mode: StereoMode::Off,
+            stereo_iod: 0.5,
+            stereo_convergence: 30.0,
             recoil: 0.025,
+            impact_scale: 1.0,
+            projectile_lifetime: Duration::from_secs(3),
             start_transform: Default::default(),
-            impact_magnitude: 25.0,
             start_speed: 0.0,
+            thrust: 10.0,
+            angular_acceleration: 6.0,
+            angular_damping: 0.5,
+            fuel_capacity: 100.0,
+            fuel_burn: 1.0,
+            fuel_recharge: 0.5,
+            flight_assist: true,
+            approach_distance: 10.0,
+            autopilot_speed: 20.0,
         }
     }
 }
 
-#[derive(Component)]
+#[derive(Component, Clone)]
+pub struct Projectile {
+    pub kind: ProjectileKind,
+    pub impulse: f32,
+    pub speed: f32,
+    pub velocity: Vec3,
+}
+
+/// A projectile with a target homes in on it.
+#[derive(Component, Clone, Copy)]
 pub struct ProjectileTarget {
     pub planet: Entity,
     pub local_direction: Vec3,
@@ -74,18 +149,62 @@ pub struct Blink {
     pub start_angle: f64, // what the! not-zero seems to break.
 }
 
-#[derive(Component)]
-pub struct ProjectileExplosion {
-    pub rising: bool,
-}
-
 #[derive(Default)]
 pub struct Despawned(HashSet<Entity>);
 
-pub fn move_forward(mut query: Query<(&mut Transform, &Spacecraft)>, time: Res<Time>) {
-    for (mut transform, spacecraft) in query.iter_mut() {
-        let direction = transform.local_z();
-        transform.translation -= direction * time.delta_seconds() * spacecraft.speed;
+/// Carry out what `control` asked of the thrusters and reaction wheels.
+pub fn fly(
+    mut query: Query<(&mut Transform, &mut Spacecraft)>,
+    config: Res<SpacecraftConfig>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+    if dt == 0.0 {
+        return;
+    }
+    for (mut transform, mut spacecraft) in query.iter_mut() {
+        // Reaction wheels run on their own power: turning is free, but only so quick.
+        let max_turn = Vec3::splat(config.angular_acceleration * dt);
+        let mut angular_velocity =
+            spacecraft.angular_velocity * (1.0 - config.angular_damping * dt).max(0.0);
+        for axis in 0..3 {
+            let target = spacecraft.turn[axis];
+            if target == 0.0 && !spacecraft.flight_assist {
+                continue;
+            }
+            angular_velocity[axis] +=
+                (target - angular_velocity[axis]).clamp(-max_turn[axis], max_turn[axis]);
+        }
+        spacecraft.angular_velocity = angular_velocity;
+        let rotation = angular_velocity * dt;
+        let local_x = transform.local_x();
+        let local_y = transform.local_y();
+        let local_z = transform.local_z();
+        transform.rotate(Quat::from_axis_angle(local_x, rotation.x));
+        transform.rotate(Quat::from_axis_angle(local_z, rotation.z));
+        transform.rotate(Quat::from_axis_angle(local_y, rotation.y));
+
+        // Thrusters burn fuel, in proportion to how hard they are pushed.
+        let local_velocity = transform.rotation.inverse() * spacecraft.velocity;
+        let mut burn = Vec3::ZERO;
+        for axis in 0..3 {
+            let thrust = spacecraft.thrust[axis];
+            burn[axis] = if thrust == 0.0 && spacecraft.flight_assist {
+                (-local_velocity[axis] / (config.thrust * dt)).clamp(-1.0, 1.0)
+            } else {
+                thrust.clamp(-1.0, 1.0)
+            };
+        }
+        let fuel_needed = (burn.x.abs() + burn.y.abs() + burn.z.abs()) * config.fuel_burn * dt;
+        if fuel_needed > spacecraft.fuel {
+            // Sputter along on what is left.
+            burn *= spacecraft.fuel / fuel_needed;
+        }
+        spacecraft.fuel = (spacecraft.fuel - fuel_needed.min(spacecraft.fuel)
+            + config.fuel_recharge * dt)
+            .min(config.fuel_capacity);
+        spacecraft.velocity += transform.rotation * burn * config.thrust * dt;
+        transform.translation += spacecraft.velocity * dt;
     }
 }
 
@@ -112,35 +231,17 @@ pub fn spacecraft_setup(
     let spacecraft = commands
         .spawn(TransformBundle::from_transform(config.start_transform))
         .insert(VisibilityBundle::default())
-        .insert(Spacecraft {
-            speed: config.start_speed,
-        })
+        .insert(Spacecraft::new(&config))
+        .insert(Weapon::default())
         .with_children(|child| {
-            if config.stereo_enabled {
-                let offset = config.stereo_iod / 2.0;
-                child
-                    .spawn(Camera3dBundle {
-                        transform: Transform::from_xyz(offset, 0.0, 0.0),
-                        ..default()
-                    })
-                    .insert(LeftCamera);
-                child
-                    .spawn(Camera3dBundle {
-                        transform: Transform::from_xyz(-offset, 0.0, 0.0),
-                        camera: Camera::default(),
-                        camera_3d: Camera3d {
-                            clear_color: ClearColorConfig::None,
-                            ..default()
-                        },
-                        ..default()
-                    })
-                    .insert(RightCamera);
-            } else {
-                child.spawn(Camera3dBundle {
-                    transform: Transform::from_xyz(0.0, 0.0, 0.0).looking_at(-Vec3::Z, Vec3::Y),
-                    ..default()
-                });
-            }
+            child
+                .spawn(PbrBundle {
+                    mesh: meshes.add(Mesh::from(shape::Box::new(0.6, 0.2, 1.2))),
+                    material: materials.add(Color::SILVER.into()),
+                    visibility: Visibility::Hidden,
+                    ..Default::default()
+                })
+                .insert(SpacecraftHull);
             child
                 // Crosshairs "bead"
                 .spawn(PbrBundle {
@@ -259,63 +360,180 @@ pub fn handle_hot_planet(
 #[derive(Event)]
 pub struct FireProjectileEvent;
 
-pub fn fire_on_hot_planet(
+/// A projectile with neither a target nor a lifetime. The caller adds whichever it needs.
+pub fn spawn_projectile(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    radius: f32,
+    translation: Vec3,
+    projectile: Projectile,
+) -> Entity {
+    commands
+        .spawn(PbrBundle {
+            mesh: meshes.add(
+                Mesh::try_from(shape::Icosphere {
+                    radius,
+                    ..Default::default()
+                })
+                .unwrap(),
+            ),
+            material: materials.add(projectile.kind.color().into()),
+            transform: Transform::from_translation(translation),
+            ..Default::default()
+        })
+        .insert(projectile)
+        .insert(RigidBody::Dynamic)
+        .insert(Collider::ball(0.001))
+        .insert(ActiveEvents::COLLISION_EVENTS)
+        .insert(Sensor)
+        .id()
+}
+
+/// Every shot launches straight ahead. If the crosshairs are on a planet, the shot homes in on it.
+pub fn fire_projectiles(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    mut spacecraft_query: Query<&mut Transform, With<Spacecraft>>,
+    mut spacecraft_query: Query<(&mut Transform, &mut Weapon, &Spacecraft)>,
     config: Res<SpacecraftConfig>,
     mut hot_planet_events: EventReader<HotPlanetEvent>,
     mut fire_projectile_events: EventReader<FireProjectileEvent>,
 ) {
-    for &HotPlanetEvent {
-        planet,
-        local_direction,
-    } in hot_planet_events.read()
-    {
-        for _ in fire_projectile_events.read() {
-            let mut spacecraft_transform = spacecraft_query
-                .get_single_mut()
-                .expect("Did not find spacecraft transform");
-            debug!("Firing at planet {planet:?}, planet-local direction to target: {local_direction:?}");
-            commands
-                // Spawn projectile
-                .spawn(PbrBundle {
-                    mesh: meshes.add(
-                        Mesh::try_from(shape::Icosphere {
-                            radius: config.projectile_radius,
-                            ..Default::default()
-                        })
-                        .unwrap(),
-                    ),
-                    material: materials.add(Color::WHITE.into()),
-                    transform: Transform::from_translation(spacecraft_transform.translation),
-                    ..Default::default()
-                })
+    let target = hot_planet_events
+        .read()
+        .last()
+        .map(|event| (event.planet, event.local_direction));
+    for _ in fire_projectile_events.read() {
+        let (mut spacecraft_transform, mut weapon, spacecraft) = spacecraft_query
+            .get_single_mut()
+            .expect("Did not find spacecraft transform");
+        if !weapon.ready() {
+            continue;
+        }
+        weapon.fire();
+        match target {
+            Some((planet, local_direction)) => debug!(
+                "Firing {} at planet {planet:?}, planet-local direction to target: {local_direction:?}",
+                weapon.kind
+            ),
+            None => debug!("Firing {} at nothing in particular", weapon.kind),
+        }
+        let mut rng = rand::thread_rng();
+        for pellet in 0..weapon.pellets {
+            // The first pellet goes where it was aimed, the rest scatter around it.
+            let scatter = if pellet == 0 {
+                Vec3::ZERO
+            } else {
+                (Vec3::new(rng.gen(), rng.gen(), rng.gen()) - 0.5) * 0.6
+            };
+            let direction = (spacecraft_transform.forward() + scatter * 0.1).normalize();
+            let projectile = spawn_projectile(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                config.projectile_radius,
+                spacecraft_transform.translation,
+                Projectile {
+                    kind: weapon.kind,
+                    impulse: weapon.hit_impulse(&config),
+                    speed: weapon.muzzle_speed,
+                    velocity: spacecraft.velocity + direction * weapon.muzzle_speed,
+                },
+            );
+            match target {
                 // The projectile carries its "target" as a component.
-                .insert(ProjectileTarget {
-                    planet,
-                    local_direction,
-                })
-                .insert(RigidBody::Dynamic)
-                .insert(Collider::ball(0.001))
-                .insert(ActiveEvents::COLLISION_EVENTS)
-                .insert(Sensor);
-
-            // Your spacecraft recoils (jiggles randomly) when you fire.
-            if config.recoil != 0.0 {
-                let mut rng = rand::thread_rng();
-                let bump_x = (rng.gen::<f32>() - 0.5) * config.recoil;
-                let bump_y = (rng.gen::<f32>() - 0.5) * config.recoil;
-                let bump_z = (rng.gen::<f32>() - 0.5) * config.recoil;
-                spacecraft_transform.rotate(Quat::from_euler(
-                    EulerRot::XYZ,
-                    bump_x,
-                    bump_y,
-                    bump_z,
-                ));
+                Some((planet, local_direction)) => {
+                    commands.entity(projectile).insert(ProjectileTarget {
+                        planet,
+                        local_direction: (local_direction + scatter).normalize(),
+                    });
+                }
+                // A miss flies on until it burns out.
+                None => {
+                    commands.entity(projectile).insert(DespawnTimer {
+                        ttl: Timer::new(config.projectile_lifetime, TimerMode::Once),
+                        simulated: true,
+                    });
+                }
             }
         }
+
+        // Your spacecraft recoils (jiggles randomly) when you fire.
+        if config.recoil != 0.0 {
+            let bump_x = (rng.gen::<f32>() - 0.5) * config.recoil;
+            let bump_y = (rng.gen::<f32>() - 0.5) * config.recoil;
+            let bump_z = (rng.gen::<f32>() - 0.5) * config.recoil;
+            spacecraft_transform.rotate(Quat::from_euler(EulerRot::XYZ, bump_x, bump_y, bump_z));
+        }
+    }
+}
+
+/// The ring that flashes in front of the spacecraft when it can't fire, or just did.
+#[derive(Resource, Default)]
+pub struct ShotFeedbackAssets {
+    ring: Handle<Mesh>,
+    fired: Handle<StandardMaterial>,
+    jammed: Handle<StandardMaterial>,
+}
+
+pub fn shot_feedback_setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let ring = meshes.add(Mesh::from(shape::Torus {
+        radius: 0.08,
+        ring_radius: 0.005,
+        ..default()
+    }));
+    let mut emissive = |color: Color| {
+        materials.add(StandardMaterial {
+            emissive: color * 5.0,
+            ..default()
+        })
+    };
+    commands.insert_resource(ShotFeedbackAssets {
+        ring,
+        fired: emissive(Color::ORANGE),
+        jammed: emissive(Color::RED),
+    });
+}
+
+/// Flash the crosshairs when a shot goes out with no planet in them to home in on (amber), or
+/// when the trigger is pulled on a jammed gun (red).
+pub fn shot_feedback(
+    mut commands: Commands,
+    assets: Res<ShotFeedbackAssets>,
+    spacecraft_query: Query<(Entity, &Weapon), With<Spacecraft>>,
+    hot_planet_events: EventReader<HotPlanetEvent>,
+    mut fire_projectile_events: EventReader<FireProjectileEvent>,
+) {
+    if fire_projectile_events.read().next().is_none() {
+        return;
+    }
+    for (spacecraft, weapon) in spacecraft_query.iter() {
+        let material = if weapon.jammed {
+            &assets.jammed
+        } else if weapon.ready() && hot_planet_events.is_empty() {
+            &assets.fired
+        } else {
+            continue;
+        };
+        let flash = commands
+            .spawn(PbrBundle {
+                mesh: assets.ring.clone(),
+                material: material.clone(),
+                transform: Transform::from_xyz(0.0, 0.0, -7.0)
+                    .with_rotation(Quat::from_rotation_x(std::f32::consts::FRAC_PI_2)),
+                ..default()
+            })
+            .insert(DespawnTimer {
+                ttl: Timer::from_seconds(0.1, TimerMode::Once),
+                simulated: false,
+            })
+            .id();
+        commands.entity(spacecraft).add_child(flash);
     }
 }
 
@@ -324,6 +542,8 @@ pub struct ProjectileCollisionEvent {
     pub planet: Entity,
     pub projectile: Entity,
     pub local_impact_site: Vec3,
+    pub kind: ProjectileKind,
+    pub impulse: f32,
 }
 
 // FIXME: need to handle in-flight projectile whose planet disappears.
@@ -336,94 +556,49 @@ pub fn handle_projectile_despawn(
     }
 }
 
-pub fn spawn_projectile_explosion_animation(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    projectile_query: Query<&ProjectileTarget>,
-    planet_query: Query<&Transform, With<Momentum>>,
-    mut projectile_events: EventReader<ProjectileCollisionEvent>,
-) {
-    for event in projectile_events.read() {
-        if let Ok(projectile_target) = projectile_query.get(event.projectile) {
-            if let Ok(planet_transform) = planet_query.get(event.planet) {
-                // FIXME: WHY does local_impact_site need any scaling??
-                let local_impact_site =
-                    event.local_impact_site / (planet_transform.scale.length() / SQRT_3);
-                // An explosion is a sphere centered on the planet surface at the impact site that grows and then shrinks to simulate a "fireball".
-                let explosion = commands
-                    .spawn(PbrBundle {
-                        mesh: meshes.add(
-                            Mesh::try_from(shape::Icosphere {
-                                radius: 0.2,
-                                ..Default::default()
-                            })
-                            .unwrap(),
-                        ),
-                        material: materials.add(StandardMaterial {
-                            base_color: Color::YELLOW,
-                            perceptual_roughness: 0.99,
-                            ..default()
-                        }),
-                        transform: Transform::from_translation(local_impact_site),
-                        ..Default::default()
-                    })
-                    .insert(ProjectileExplosion { rising: true })
-                    .id();
-                commands.entity(event.planet).add_child(explosion);
-                debug!(
-                    "Explosion animation entity {explosion:?} spawned and now a child of planet {:?} with local coordiantes {:?}",
-                    projectile_target.planet,
-		    local_impact_site,
-                );
-            } else {
-                warn!(
-                    "While spawning explosion animation: planet {:?} not found",
-                    event.planet
-                );
-            }
-        } else {
-            warn!(
-                "While spawning explosion animation: projectile {:?} not found",
-                event.projectile
-            );
-        }
-    }
-}
-
 /// When a projectile collides with a planet, the planet's momentum is affected.
 pub fn transfer_projectile_momentum(
-    planet_query: Query<&Momentum, Without<ProjectileTarget>>,
+    mut planet_query: Query<&mut Momentum, Without<Projectile>>,
     mut projectile_events: EventReader<ProjectileCollisionEvent>,
     mut delta_events: EventWriter<DeltaEvent>,
-    config: Res<SpacecraftConfig>,
 ) {
     for event in projectile_events.read() {
-        if let Ok(planet_momentum) = planet_query.get(event.planet) {
-            let delta_v = -event.local_impact_site.normalize() * config.impact_magnitude
-                / planet_momentum.mass;
+        if let Ok(mut planet_momentum) = planet_query.get_mut(event.planet) {
+            let delta = projectile_delta(
+                event.planet,
+                &mut planet_momentum,
+                event.kind,
+                event.impulse,
+                event.local_impact_site,
+            );
             debug!(
-                "Projectile {:?} impacting planet {:?}, delta_v={:?}",
-                event.projectile, event.planet, delta_v,
+                "{} projectile {:?} impacting planet {:?}, delta_v={:?}",
+                event.kind, event.projectile, event.planet, delta.delta_v,
             );
-            delta_events.send(DeltaEvent {
-                entity: event.planet,
-                delta_p: Vec3::ZERO,
-                delta_v,
-                delta_s: 1.0,
-                force_ro: Vec3::ZERO,
-            });
+            delta_events.send(delta);
         }
     }
 }
 
-/// Projectiles are moved toward their target planet by a constant velocity.
+/// Projectiles are moved toward their target planet by a constant velocity. Those without a
+/// target keep going the way they were fired.
 pub fn move_projectiles(
-    mut projectile_query: Query<(Entity, &mut Transform, &ProjectileTarget)>,
-    planet_query: Query<(&Transform, &mut Momentum, Entity), Without<ProjectileTarget>>,
-    time: Res<Time>,
+    mut projectile_query: Query<(
+        Entity,
+        &mut Transform,
+        &mut Projectile,
+        Option<&ProjectileTarget>,
+    )>,
+    planet_query: Query<(&Transform, &mut Momentum, Entity), Without<Projectile>>,
+    clock: Res<SimulationClock>,
 ) {
-    for (projectile, mut projectile_transform, target) in projectile_query.iter_mut() {
+    for (projectile, mut projectile_transform, mut projectile_state, target) in
+        projectile_query.iter_mut()
+    {
+        let Some(target) = target else {
+            projectile_transform.translation += projectile_state.velocity * clock.delta_seconds();
+            continue;
+        };
         if let Ok((planet_transform, planet_momentum, _)) = planet_query.get(target.planet) {
             let planet_radius = mass_to_radius(planet_momentum.mass);
             let target_coordinates =
@@ -431,13 +606,11 @@ pub fn move_projectiles(
             let translation_to_target = target_coordinates - projectile_transform.translation;
             let distance = translation_to_target.length();
             let direction = translation_to_target.normalize();
-            // Hardcoding the speed of a projectile.
-            let speed_coefficient = 120.0;
-            let absolute_velocity =
-                direction * speed_coefficient * ((distance + 30.0) / (distance + 1.0));
+            let absolute_velocity = direction * homing_speed(projectile_state.speed, distance);
             // constant velocity relative planet
             let velocity = absolute_velocity + planet_momentum.velocity;
-            let mut translation = velocity * time.delta_seconds();
+            projectile_state.velocity = velocity;
+            let mut translation = velocity * clock.delta_seconds();
             // FIXME: If the distance to the target is less than the distance the projectile will travel, then instead just travel a bit more than that distance to ensure collision. The planet's radius should be part of this calculation.
             if translation.length() > distance {
                 translation = translation_to_target * 1.1;
@@ -453,31 +626,6 @@ pub fn move_projectiles(
     }
 }
 
-/// Animate the "explosion" of the collision.
-pub fn animate_projectile_explosion(
-    mut commands: Commands,
-    mut explosion_query: Query<(Entity, &mut Transform, &mut ProjectileExplosion)>,
-    time: Res<Time>,
-) {
-    for (entity, mut transform, mut explosion) in explosion_query.iter_mut() {
-        let animation_direction = if explosion.rising { 3.5 } else { -2.0 };
-        transform.scale += Vec3::splat(1.0) * 0.2 * animation_direction * time.delta_seconds();
-        if transform.scale.length() > 3.0 {
-            explosion.rising = false;
-        }
-        let mut coords = [0.0; 3];
-        transform.scale.write_to_slice(&mut coords);
-        // A decaying explosion is scaled down in each frame. When any of the coordinates become negative (scaled downward past zero), then the explosion entity is despawned.
-        for d in coords {
-            if d < 0.0 {
-                debug!("despawning explosion entity {:?}", entity);
-                commands.entity(entity).despawn();
-                return;
-            }
-        }
-    }
-}
-
 #[derive(Event)]
 pub struct HotPlanetEvent {
     pub planet: Entity,
@@ -489,8 +637,15 @@ pub fn signal_hot_planet(
     planet_query: Query<&Transform, With<Momentum>>,
     spacecraft_query: Query<&Transform, With<Spacecraft>>,
     rapier_context: Res<RapierContext>,
+    mut locked: ResMut<LockedTarget>,
     mut hot_planet_events: EventWriter<HotPlanetEvent>,
 ) {
+    // Planets removed by a round teardown never pass through `handle_despawn_planet`.
+    if let Some(planet) = locked.planet {
+        if !planet_query.contains(planet) {
+            locked.planet = None;
+        }
+    }
     for pov in spacecraft_query.iter() {
         let ray_origin = pov.translation;
         let ray_direction = -1.0 * pov.local_z();
@@ -502,17 +657,27 @@ pub fn signal_hot_planet(
             QueryFilter::only_dynamic(),
         );
 
-        if let Some((planet, distance)) = intersection {
-            if let Ok(planet_transform) = planet_query.get(planet) {
-                let global_impact_site = ray_origin + (ray_direction * distance);
-                let local_direction =
-                    (global_impact_site - planet_transform.translation).normalize();
-                let event = HotPlanetEvent {
+        let mut hot_planet = intersection.and_then(|(planet, distance)| {
+            let planet_transform = planet_query.get(planet).ok()?;
+            let global_impact_site = ray_origin + (ray_direction * distance);
+            let local_direction = (global_impact_site - planet_transform.translation).normalize();
+            Some(HotPlanetEvent {
+                planet,
+                local_direction,
+            })
+        });
+        if let Some(planet) = locked.planet {
+            // Off-center, aim for the side facing us.
+            if hot_planet.as_ref().map(|event| event.planet) != Some(planet) {
+                let planet_transform = planet_query.get(planet).unwrap();
+                hot_planet = Some(HotPlanetEvent {
                     planet,
-                    local_direction,
-                };
-                hot_planet_events.send(event);
+                    local_direction: (ray_origin - planet_transform.translation).normalize(),
+                });
             }
         }
+        if let Some(event) = hot_planet {
+            hot_planet_events.send(event);
+        }
     }
 }
@@ -0,0 +1,274 @@
+use bevy::{
+    core_pipeline::clear_color::ClearColorConfig,
+    prelude::*,
+    reflect::TypePath,
+    render::{
+        camera::{RenderTarget, Viewport},
+        render_resource::{
+            AsBindGroup, Extent3d, ShaderRef, TextureDescriptor, TextureDimension, TextureFormat,
+            TextureUsages,
+        },
+        view::NoFrustumCulling,
+    },
+    sprite::{Material2d, MaterialMesh2dBundle},
+    window::PrimaryWindow,
+};
+use std::fmt;
+
+use super::{LeftCamera, RightCamera, SpacecraftConfig};
+use crate::bindings::{Action, ActionState};
+
+pub const ANAGLYPH_SHADER_HANDLE: Handle<Shader> = Handle::weak_from_u128(7251630946180417203);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StereoMode {
+    /// One eye, the whole window.
+    #[default]
+    Off,
+    /// Left eye on the left half of the window, right eye on the right. For parallel viewing,
+    /// or a 3D display.
+    SideBySide,
+    /// Left eye on the top half, right eye on the bottom.
+    TopBottom,
+    /// Both eyes over the whole window, for red/cyan glasses.
+    Anaglyph,
+    /// Side-by-side with the halves swapped, for viewing cross-eyed.
+    CrossEyed,
+}
+
+impl StereoMode {
+    pub fn next(&self) -> Self {
+        match self {
+            StereoMode::Off => StereoMode::SideBySide,
+            StereoMode::SideBySide => StereoMode::TopBottom,
+            StereoMode::TopBottom => StereoMode::Anaglyph,
+            StereoMode::Anaglyph => StereoMode::CrossEyed,
+            StereoMode::CrossEyed => StereoMode::Off,
+        }
+    }
+}
+
+impl fmt::Display for StereoMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StereoMode::Off => write!(f, "Off"),
+            StereoMode::SideBySide => write!(f, "Side-by-side"),
+            StereoMode::TopBottom => write!(f, "Top-bottom"),
+            StereoMode::Anaglyph => write!(f, "Anaglyph"),
+            StereoMode::CrossEyed => write!(f, "Cross-eyed"),
+        }
+    }
+}
+
+/// Combines the two eyes' images into one.
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct AnaglyphMaterial {
+    #[texture(0)]
+    #[sampler(1)]
+    left: Handle<Image>,
+    #[texture(2)]
+    #[sampler(3)]
+    right: Handle<Image>,
+}
+
+impl Material2d for AnaglyphMaterial {
+    fn vertex_shader() -> ShaderRef {
+        ANAGLYPH_SHADER_HANDLE.into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        ANAGLYPH_SHADER_HANDLE.into()
+    }
+}
+
+/// Draws the anaglyph to the window. Only active in `StereoMode::Anaglyph`.
+#[derive(Component)]
+pub struct AnaglyphCamera;
+
+/// What each eye renders to in `StereoMode::Anaglyph`. Kept the size of the window.
+#[derive(Resource)]
+pub struct AnaglyphTargets {
+    pub left: Handle<Image>,
+    pub right: Handle<Image>,
+}
+
+pub fn stereo_setup(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<AnaglyphMaterial>>,
+) {
+    // Sized properly by `apply_stereo` once we know how big the window is.
+    let targets = AnaglyphTargets {
+        left: images.add(eye_image(UVec2::ONE)),
+        right: images.add(eye_image(UVec2::ONE)),
+    };
+    commands
+        .spawn(Camera2dBundle {
+            camera: Camera {
+                order: 2,
+                is_active: false,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(AnaglyphCamera);
+    commands
+        .spawn(MaterialMesh2dBundle {
+            mesh: meshes.add(shape::Quad::new(Vec2::splat(2.0)).into()).into(),
+            material: materials.add(AnaglyphMaterial {
+                left: targets.left.clone(),
+                right: targets.right.clone(),
+            }),
+            ..default()
+        })
+        .insert(NoFrustumCulling);
+    commands.insert_resource(targets);
+}
+
+fn eye_image(size: UVec2) -> Image {
+    let size = Extent3d {
+        width: size.x,
+        height: size.y,
+        ..default()
+    };
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: None,
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Bgra8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        },
+        ..default()
+    };
+    image.resize(size);
+    image
+}
+
+/// Cycle the stereo mode, and widen (or narrow) the eyes and push out (or pull in) the point
+/// where they converge.
+pub fn adjust_stereo(
+    actions: Res<ActionState>,
+    mut config: ResMut<SpacecraftConfig>,
+    time: Res<Time>,
+) {
+    if actions.just_pressed(Action::StereoMode) {
+        config.stereo_mode = config.stereo_mode.next();
+        info!("Stereo: {}", config.stereo_mode);
+    }
+    let dt = time.delta_seconds();
+    let separation = actions.get(Action::StereoSeparation);
+    let iod = (config.stereo_iod + separation.rate * dt + separation.delta).max(0.0);
+    // Convergence matters from a few units out to the far side of the system, so it scales.
+    let convergence = actions.get(Action::StereoConvergence);
+    let distance =
+        (config.stereo_convergence * (convergence.rate * dt + convergence.delta).exp()).max(1.0);
+    if iod != config.stereo_iod || distance != config.stereo_convergence {
+        config.stereo_iod = iod;
+        config.stereo_convergence = distance;
+    }
+}
+
+type EyeCameras<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Camera,
+        &'static mut Camera3d,
+        &'static mut Transform,
+        Has<RightCamera>,
+    ),
+    Or<(With<LeftCamera>, With<RightCamera>)>,
+>;
+
+/// Place the eyes and lay out what they render to for the current stereo mode. The eyes are toed
+/// in to meet `stereo_convergence` ahead: things nearer than that stand out of the screen.
+pub fn apply_stereo(
+    config: Res<SpacecraftConfig>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut eye_query: EyeCameras,
+    mut anaglyph_query: Query<&mut Camera, (With<AnaglyphCamera>, Without<Camera3d>)>,
+    targets: Res<AnaglyphTargets>,
+    mut images: ResMut<Assets<Image>>,
+    mut applied: Local<Option<(StereoMode, UVec2)>>,
+) {
+    let mode = config.stereo_mode;
+    let half_iod = match mode {
+        StereoMode::Off => 0.0,
+        _ => config.stereo_iod / 2.0,
+    };
+    let toe_in = (half_iod / config.stereo_convergence).atan();
+    for (_, _, mut transform, right) in eye_query.iter_mut() {
+        let side = if right { 1.0 } else { -1.0 };
+        let eye = Transform::from_xyz(side * half_iod, 0.0, 0.0)
+            .with_rotation(Quat::from_rotation_y(side * toe_in));
+        if *transform != eye {
+            *transform = eye;
+        }
+    }
+
+    // The rest only changes with the mode or the window size.
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+    let size = UVec2::new(window.physical_width(), window.physical_height());
+    if size.min_element() == 0 || *applied == Some((mode, size)) {
+        return;
+    }
+    *applied = Some((mode, size));
+    debug!("Stereo {mode} at {size}");
+
+    if mode == StereoMode::Anaglyph {
+        for handle in [&targets.left, &targets.right] {
+            if let Some(image) = images.get_mut(handle) {
+                image.resize(Extent3d {
+                    width: size.x,
+                    height: size.y,
+                    ..default()
+                });
+            }
+        }
+    }
+    let half_width = UVec2::new(size.x / 2, size.y);
+    let half_height = UVec2::new(size.x, size.y / 2);
+    for (mut camera, mut camera_3d, _, right) in eye_query.iter_mut() {
+        // The size and position of this eye's part of the window, if it only gets part.
+        let split = match mode {
+            StereoMode::Off | StereoMode::Anaglyph => None,
+            StereoMode::SideBySide => Some((half_width, right)),
+            StereoMode::CrossEyed => Some((half_width, !right)),
+            StereoMode::TopBottom => Some((half_height, right)),
+        }
+        .map(|(physical_size, second)| Viewport {
+            physical_position: if second {
+                size - physical_size
+            } else {
+                UVec2::ZERO
+            },
+            physical_size,
+            ..default()
+        });
+        // In a split window the left eye clears it for both.
+        camera_3d.clear_color = if right && split.is_some() {
+            ClearColorConfig::None
+        } else {
+            ClearColorConfig::Default
+        };
+        camera.viewport = split;
+        camera.target = match (mode, right) {
+            (StereoMode::Anaglyph, false) => RenderTarget::Image(targets.left.clone()),
+            (StereoMode::Anaglyph, true) => RenderTarget::Image(targets.right.clone()),
+            _ => RenderTarget::default(),
+        };
+        camera.is_active = !right || mode != StereoMode::Off;
+    }
+    for mut camera in anaglyph_query.iter_mut() {
+        camera.is_active = mode == StereoMode::Anaglyph;
+    }
+}
@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+use std::fmt;
+
+use super::{HotPlanetEvent, Spacecraft};
+use crate::bindings::{Action, ActionState};
+use crate::physics::Momentum;
+
+/// The order `Action::NextTarget` and `Action::PreviousTarget` step through the planets in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TargetSort {
+    #[default]
+    Nearest,
+    Largest,
+    /// Closing fastest for its distance, i.e. soonest to arrive.
+    MostThreatening,
+}
+
+impl TargetSort {
+    pub fn next(&self) -> Self {
+        match self {
+            TargetSort::Nearest => TargetSort::Largest,
+            TargetSort::Largest => TargetSort::MostThreatening,
+            TargetSort::MostThreatening => TargetSort::Nearest,
+        }
+    }
+}
+
+impl fmt::Display for TargetSort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TargetSort::Nearest => write!(f, "nearest"),
+            TargetSort::Largest => write!(f, "largest"),
+            TargetSort::MostThreatening => write!(f, "most threatening"),
+        }
+    }
+}
+
+/// While a planet is locked, it is the hot planet whether or not the crosshairs are on it.
+#[derive(Resource, Debug, Default)]
+pub struct LockedTarget {
+    pub planet: Option<Entity>,
+    pub sort: TargetSort,
+}
+
+pub fn lock_target(
+    actions: Res<ActionState>,
+    mut locked: ResMut<LockedTarget>,
+    mut hot_planet_events: EventReader<HotPlanetEvent>,
+    spacecraft_query: Query<(&Transform, &Spacecraft)>,
+    planet_query: Query<(Entity, &Transform, &Momentum)>,
+) {
+    let hot_planet = hot_planet_events.read().last().map(|event| event.planet);
+    if actions.just_pressed(Action::LockTarget) {
+        locked.planet = match locked.planet {
+            Some(_) => None,
+            None => hot_planet,
+        };
+        debug!("Target lock: {:?}", locked.planet);
+    }
+
+    let step = if actions.just_pressed(Action::TargetSort) {
+        locked.sort = locked.sort.next();
+        debug!("Sorting targets by {}", locked.sort);
+        locked.planet = None;
+        1
+    } else if actions.just_pressed(Action::NextTarget) {
+        1
+    } else if actions.just_pressed(Action::PreviousTarget) {
+        -1
+    } else {
+        return;
+    };
+    let Ok((transform, spacecraft)) = spacecraft_query.get_single() else {
+        return;
+    };
+    let mut candidates = planet_query
+        .iter()
+        .map(|(planet, planet_transform, momentum)| {
+            let offset = planet_transform.translation - transform.translation;
+            let closing =
+                -(momentum.velocity - spacecraft.velocity).dot(offset.normalize_or_zero());
+            let key = match locked.sort {
+                TargetSort::Nearest => offset.length(),
+                TargetSort::Largest => -momentum.mass,
+                TargetSort::MostThreatening => -closing / offset.length().max(1.0),
+            };
+            (planet, key)
+        })
+        .collect::<Vec<_>>();
+    if candidates.is_empty() {
+        return;
+    }
+    candidates.sort_by(|a, b| a.1.total_cmp(&b.1));
+    let count = candidates.len() as i32;
+    let index = match candidates
+        .iter()
+        .position(|(planet, _)| Some(*planet) == locked.planet)
+    {
+        Some(index) => (index as i32 + step).rem_euclid(count),
+        None if step > 0 => 0,
+        None => count - 1,
+    };
+    locked.planet = Some(candidates[index as usize].0);
+    debug!("Locked onto {:?} ({})", locked.planet, locked.sort);
+}
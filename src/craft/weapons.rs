@@ -0,0 +1,184 @@
+use bevy::prelude::*;
+use std::fmt;
+
+use super::{Spacecraft, SpacecraftConfig};
+use crate::bindings::{Action, ActionState};
+use crate::physics::{DeltaEvent, Momentum};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProjectileKind {
+    /// Knocks the planet away from the impact site.
+    #[default]
+    Push,
+    /// A tractor shot: drags the planet toward the impact site.
+    Pull,
+    /// Adds mass (and no momentum), so the planet grows and slows.
+    Mass,
+    /// Several weak push shots fanned out over the planet.
+    Spread,
+}
+
+impl ProjectileKind {
+    pub const ALL: [ProjectileKind; 4] = [
+        ProjectileKind::Push,
+        ProjectileKind::Pull,
+        ProjectileKind::Mass,
+        ProjectileKind::Spread,
+    ];
+
+    pub fn next(&self) -> Self {
+        let index = Self::ALL.iter().position(|kind| kind == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            ProjectileKind::Push => Color::WHITE,
+            ProjectileKind::Pull => Color::CYAN,
+            ProjectileKind::Mass => Color::ORANGE,
+            ProjectileKind::Spread => Color::YELLOW,
+        }
+    }
+}
+
+impl fmt::Display for ProjectileKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProjectileKind::Push => write!(f, "Push"),
+            ProjectileKind::Pull => write!(f, "Tractor"),
+            ProjectileKind::Mass => write!(f, "Mass"),
+            ProjectileKind::Spread => write!(f, "Spread"),
+        }
+    }
+}
+
+/// The spacecraft's gun. Every shot heats it up; at full heat it jams until it has cooled off.
+#[derive(Component, Debug, Clone)]
+pub struct Weapon {
+    pub kind: ProjectileKind,
+    /// Shots per second.
+    pub rate_of_fire: f32,
+    pub heat_per_shot: f32,
+    /// Heat shed per second.
+    pub cooling: f32,
+    pub muzzle_speed: f32,
+    /// Momentum delivered by each projectile. For `ProjectileKind::Mass`, the mass added.
+    pub impulse: f32,
+    /// Projectiles per shot.
+    pub pellets: u32,
+    /// From cold (0.0) to jammed (1.0).
+    pub heat: f32,
+    pub jammed: bool,
+    // Seconds until the next shot.
+    reload: f32,
+}
+
+impl Weapon {
+    pub fn new(kind: ProjectileKind) -> Self {
+        let (rate_of_fire, heat_per_shot, muzzle_speed, impulse, pellets) = match kind {
+            ProjectileKind::Push => (4.0, 0.08, 120.0, 5.0, 1),
+            ProjectileKind::Pull => (2.0, 0.15, 90.0, 5.0, 1),
+            ProjectileKind::Mass => (1.0, 0.3, 60.0, 2.0, 1),
+            ProjectileKind::Spread => (1.5, 0.25, 100.0, 1.5, 5),
+        };
+        Self {
+            kind,
+            rate_of_fire,
+            heat_per_shot,
+            cooling: 0.3,
+            muzzle_speed,
+            impulse,
+            pellets,
+            heat: 0.0,
+            jammed: false,
+            reload: 0.0,
+        }
+    }
+
+    /// Change projectile type. The gun stays as hot as it was.
+    pub fn switch_to(&mut self, kind: ProjectileKind) {
+        *self = Self {
+            heat: self.heat,
+            jammed: self.jammed,
+            reload: self.reload,
+            ..Self::new(kind)
+        };
+    }
+
+    /// What each projectile actually delivers. The server applies hits by this too, so it has
+    /// to agree with what clients fire.
+    pub fn hit_impulse(&self, config: &SpacecraftConfig) -> f32 {
+        self.impulse * config.impact_scale
+    }
+
+    pub fn ready(&self) -> bool {
+        !self.jammed && self.reload <= 0.0
+    }
+
+    pub fn fire(&mut self) {
+        self.reload = 1.0 / self.rate_of_fire;
+        self.heat += self.heat_per_shot;
+        if self.heat >= 1.0 {
+            debug!("Weapon jammed");
+            self.heat = 1.0;
+            self.jammed = true;
+        }
+    }
+}
+
+impl Default for Weapon {
+    fn default() -> Self {
+        Self::new(ProjectileKind::default())
+    }
+}
+
+pub fn switch_weapon(actions: Res<ActionState>, mut weapon_query: Query<&mut Weapon>) {
+    if actions.just_pressed(Action::NextWeapon) {
+        for mut weapon in weapon_query.iter_mut() {
+            let kind = weapon.kind.next();
+            debug!("Switching to {kind} projectiles");
+            weapon.switch_to(kind);
+        }
+    }
+}
+
+pub fn cool_weapons(mut weapon_query: Query<&mut Weapon, With<Spacecraft>>, time: Res<Time>) {
+    let dt = time.delta_seconds();
+    for mut weapon in weapon_query.iter_mut() {
+        weapon.reload -= dt;
+        weapon.heat = (weapon.heat - weapon.cooling * dt).max(0.0);
+        if weapon.jammed && weapon.heat == 0.0 {
+            debug!("Weapon cooled off");
+            weapon.jammed = false;
+        }
+    }
+}
+
+/// What a projectile of `kind` does to the planet it strikes. Mass is changed directly (and
+/// `size_planets` grows the planet to suit), everything else comes back as a `DeltaEvent`.
+pub fn projectile_delta(
+    planet: Entity,
+    momentum: &mut Momentum,
+    kind: ProjectileKind,
+    impulse: f32,
+    local_direction: Vec3,
+) -> DeltaEvent {
+    let local_direction = local_direction.normalize();
+    let delta_v = match kind {
+        ProjectileKind::Push | ProjectileKind::Spread => -local_direction * impulse / momentum.mass,
+        ProjectileKind::Pull => local_direction * impulse / momentum.mass,
+        ProjectileKind::Mass => {
+            let mass = momentum.mass + impulse;
+            // Same momentum, more mass.
+            let delta_v = momentum.velocity * (momentum.mass / mass - 1.0);
+            momentum.mass = mass;
+            delta_v
+        }
+    };
+    DeltaEvent {
+        entity: planet,
+        delta_p: Vec3::ZERO,
+        delta_v,
+        force_ro: Vec3::ZERO,
+    }
+}
@@ -1,4 +1,5 @@
-
+use bevy::prelude::*;
+use bevy::window::{CursorGrabMode, PrimaryWindow};
 use bevy_egui::{
     egui::{
         CentralPanel, Color32, FontFamily::Monospace, FontId, Frame, RichText, SidePanel,
@@ -8,7 +9,61 @@ use bevy_egui::{
 };
 use egui_extras::{Column, Size, TableBuilder};
 
-pub fn helpscreen(mut ctx: EguiContexts) {
+use crate::bindings::{Action, ActionState, InputBindings, InputSource};
+use crate::AppState;
+
+pub fn pause_on_help_key(actions: Res<ActionState>, mut next_state: ResMut<NextState<AppState>>) {
+    if actions.just_pressed(Action::Help) {
+        next_state.set(AppState::Help);
+    }
+}
+
+// Clicks are handled by `helpscreen` itself, which knows whether a button was hit.
+pub fn resume_on_help_key(actions: Res<ActionState>, mut next_state: ResMut<NextState<AppState>>) {
+    if actions.just_pressed(Action::Help) {
+        next_state.set(AppState::Playing);
+    }
+}
+
+pub fn pause_time(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+pub fn resume_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+// The click that dismissed the help screen should not also fire a projectile.
+pub fn swallow_clicks(mut mouse_buttons: ResMut<Input<MouseButton>>) {
+    mouse_buttons.reset_all();
+}
+
+pub fn grab_cursor(mut window_query: Query<&mut Window, With<PrimaryWindow>>) {
+    for mut window in window_query.iter_mut() {
+        // Neither mode is supported everywhere.
+        window.cursor.grab_mode = if cfg!(target_os = "macos") {
+            CursorGrabMode::Locked
+        } else {
+            CursorGrabMode::Confined
+        };
+        window.cursor.visible = false;
+    }
+}
+
+pub fn release_cursor(mut window_query: Query<&mut Window, With<PrimaryWindow>>) {
+    for mut window in window_query.iter_mut() {
+        window.cursor.grab_mode = CursorGrabMode::None;
+        window.cursor.visible = true;
+    }
+}
+
+pub fn helpscreen(
+    mut ctx: EguiContexts,
+    bindings: Res<InputBindings>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let mut rebind = false;
+    let mut settings = false;
     let fill_color = Color32::from_rgba_premultiplied(0, 0, 0, 240);
     TopBottomPanel::top("top_panel")
         .resizable(false)
@@ -57,6 +112,7 @@ pub fn helpscreen(mut ctx: EguiContexts) {
                 .size(Size::exact(65.0))
                 .size(Size::exact(30.0))
                 .size(Size::remainder())
+                .size(Size::exact(40.0))
                 .vertical(|mut strip| {
                     strip.cell(|ui| {
                         styled_text_label(50.0, ui, "CLICK ANYWHERE TO BEGIN!");
@@ -65,25 +121,44 @@ pub fn helpscreen(mut ctx: EguiContexts) {
                         styled_text_label(22.0, ui, ".. Input Bindings ..");
                     });
                     strip.cell(|ui| {
-                        build_table(ui);
+                        build_table(ui, &bindings);
+                    });
+                    strip.cell(|ui| {
+                        ui.horizontal(|ui| {
+                            rebind = ui
+                                .button(RichText::new("Change Bindings").color(Color32::GREEN))
+                                .clicked();
+                            settings = ui
+                                .button(RichText::new("Settings").color(Color32::GREEN))
+                                .clicked();
+                        });
                     });
                 });
         });
+
+    if rebind {
+        next_state.set(AppState::Controls);
+    } else if settings {
+        next_state.set(AppState::Settings);
+    } else if ctx.ctx_mut().input(|input| input.pointer.primary_clicked()) {
+        next_state.set(AppState::Playing);
+    }
 }
 
-fn styled_text_label(height: f32, ui: &mut egui::Ui, text: &str) {
+pub(crate) fn styled_text_label(height: f32, ui: &mut egui::Ui, text: &str) {
     ui.label(RichText::new(text).color(Color32::GREEN).font(FontId {
         size: height,
         family: Monospace,
     }));
 }
 
-fn build_table(ui: &mut egui::Ui) {
+fn build_table(ui: &mut egui::Ui, bindings: &InputBindings) {
     TableBuilder::new(ui)
         .striped(false)
         .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
-        .column(Column::initial(120.0))
-        .column(Column::initial(270.0))
+        .column(Column::initial(160.0))
+        .column(Column::initial(200.0))
+        .column(Column::initial(200.0))
         .column(Column::remainder())
         .resizable(false)
         .header(50.0, |mut header| {
@@ -93,75 +168,39 @@ fn build_table(ui: &mut egui::Ui) {
             header.col(|ui| {
                 styled_text_label(22.0, ui, "Mouse");
             });
+            header.col(|ui| {
+                styled_text_label(22.0, ui, "Gamepad");
+            });
             header.col(|ui| {
                 styled_text_label(20.0, ui, "Function");
             });
         })
         .body(|mut body| {
             let row_height = 22.0;
-            body.row(row_height, |mut row| {
-                row.col(|ui| {
-                    styled_text_label(18.0, ui, "Space");
-                });
-                row.col(|ui| {
-                    styled_text_label(18.0, ui, "Left click");
-                });
-                row.col(|ui| {
-                    styled_text_label(18.0, ui, "Fire Projectile");
-                });
-            });
-            body.row(row_height, |mut row| {
-                row.col(|ui| {
-                    styled_text_label(18.0, ui, "W / S");
-                });
-                row.col(|ui| {
-                    styled_text_label(18.0, ui, "Up / Down");
-                });
-                row.col(|ui| {
-                    styled_text_label(18.0, ui, "Pitch up / Pitch down");
-                });
-            });
-            body.row(row_height, |mut row| {
-                row.col(|ui| {
-                    styled_text_label(18.0, ui, "A / D");
-                });
-                row.col(|ui| {
-                    styled_text_label(18.0, ui, "Left / Right");
-                });
-                row.col(|ui| {
-                    styled_text_label(18.0, ui, "Yaw left / Yaw right");
-                });
-            });
-            body.row(row_height, |mut row| {
-                row.col(|ui| {
-                    styled_text_label(18.0, ui, "Z / X");
-                });
-                row.col(|_| {});
-                row.col(|ui| {
-                    styled_text_label(18.0, ui, "Roll left / Roll right");
-                });
-            });
-
-            body.row(row_height, |mut row| {
-                row.col(|ui| {
-                    styled_text_label(18.0, ui, "PgUp / PgDn");
-                });
-                row.col(|ui| {
-                    styled_text_label(18.0, ui, "Scroll wheel");
-                });
-                row.col(|ui| {
-                    styled_text_label(18.0, ui, "Speed plus / Speed minus");
-                });
-            });
-
-            body.row(row_height, |mut row| {
-                row.col(|ui| {
-                    styled_text_label(18.0, ui, "H or P");
-                });
-                row.col(|_| {});
-                row.col(|ui| {
-                    styled_text_label(18.0, ui, "[P]ause and show this [H]elp screen");
+            for action in Action::ALL {
+                let sources = |filter: fn(&InputSource) -> bool| {
+                    bindings
+                        .get(action)
+                        .iter()
+                        .filter(|binding| filter(&binding.source))
+                        .map(|binding| binding.source.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                };
+                body.row(row_height, |mut row| {
+                    row.col(|ui| {
+                        styled_text_label(18.0, ui, &sources(InputSource::is_keyboard));
+                    });
+                    row.col(|ui| {
+                        styled_text_label(18.0, ui, &sources(InputSource::is_mouse));
+                    });
+                    row.col(|ui| {
+                        styled_text_label(18.0, ui, &sources(InputSource::is_gamepad));
+                    });
+                    row.col(|ui| {
+                        styled_text_label(18.0, ui, action.description());
+                    });
                 });
-            });
+            }
         });
 }
@@ -0,0 +1,204 @@
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{Align2, Color32, Frame, Grid, RichText, Ui, Window},
+    EguiContexts,
+};
+
+use crate::bindings::{Action, InputBindings};
+use crate::clock::SimulationClock;
+use crate::craft::{
+    HotPlanetEvent, InterceptPrediction, LockedTarget, ProjectileKind, Spacecraft,
+    SpacecraftConfig, StereoMode, Weapon,
+};
+use crate::mass_to_radius;
+use crate::physics::Momentum;
+
+/// Readouts for our own spacecraft, and for the planet under the crosshairs.
+pub fn hud(
+    mut ctx: EguiContexts,
+    spacecraft_query: Query<(&Transform, &Spacecraft, &Weapon)>,
+    planet_query: Query<(&Transform, &Momentum)>,
+    mut hot_planet_events: EventReader<HotPlanetEvent>,
+    prediction: Res<InterceptPrediction>,
+    locked: Res<LockedTarget>,
+    config: Res<SpacecraftConfig>,
+) {
+    let Ok((transform, spacecraft, weapon)) = spacecraft_query.get_single() else {
+        return;
+    };
+    let hot_planet = hot_planet_events
+        .read()
+        .last()
+        .and_then(|event| planet_query.get(event.planet).ok());
+
+    let forward = transform.forward();
+    let heading = forward.x.atan2(-forward.z).to_degrees().rem_euclid(360.0);
+    let elevation = forward.y.clamp(-1.0, 1.0).asin().to_degrees();
+
+    Window::new("HUD")
+        .title_bar(false)
+        .anchor(Align2::LEFT_BOTTOM, [10.0, -10.0])
+        .resizable(false)
+        .frame(Frame::window(&ctx.ctx_mut().style()).fill(Color32::from_black_alpha(160)))
+        .show(ctx.ctx_mut(), |ui| {
+            Grid::new("spacecraft").show(ui, |ui| {
+                row(ui, "Speed", format!("{:.1}", spacecraft.velocity.length()));
+                row(
+                    ui,
+                    "Heading",
+                    format!("{heading:03.0}° / {elevation:+03.0}°"),
+                );
+                row(
+                    ui,
+                    "Throttle",
+                    format!("{:+.0}%", spacecraft.throttle * 100.0),
+                );
+                row(
+                    ui,
+                    "Fuel",
+                    format!("{:.0}%", spacecraft.fuel / config.fuel_capacity * 100.0),
+                );
+                let assist = if spacecraft.flight_assist {
+                    "on"
+                } else {
+                    "off"
+                };
+                row(ui, "Flight assist", assist.to_string());
+                if let Some(autopilot) = spacecraft.autopilot {
+                    row(ui, "Autopilot", format!("{:?}", autopilot.mode));
+                }
+                let heat = if weapon.jammed { " JAMMED" } else { "" };
+                row(
+                    ui,
+                    "Weapon",
+                    format!("{} ({:.0}% heat){heat}", weapon.kind, weapon.heat * 100.0),
+                );
+                if config.stereo_mode != StereoMode::Off {
+                    row(
+                        ui,
+                        "Stereo",
+                        format!(
+                            "{} (IOD {:.2}, converging at {:.0})",
+                            config.stereo_mode, config.stereo_iod, config.stereo_convergence
+                        ),
+                    );
+                }
+            });
+            let Some((planet_transform, momentum)) = hot_planet else {
+                return;
+            };
+            ui.separator();
+            let offset = planet_transform.translation - transform.translation;
+            let relative_velocity = momentum.velocity - spacecraft.velocity;
+            let radius = mass_to_radius(momentum.mass);
+            // When the two of us will be nearest, if nobody changes course.
+            let closest_approach =
+                -offset.dot(relative_velocity) / relative_velocity.length_squared();
+            Grid::new("target").show(ui, |ui| {
+                if locked.planet.is_some() {
+                    row(ui, "Target", format!("LOCKED ({})", locked.sort));
+                }
+                row(ui, "Distance", format!("{:.1}", offset.length() - radius));
+                row(
+                    ui,
+                    "Closing speed",
+                    format!("{:.2}", -relative_velocity.dot(offset.normalize())),
+                );
+                row(
+                    ui,
+                    "Relative velocity",
+                    format!("{:.2}", relative_velocity.length()),
+                );
+                row(ui, "Mass", format!("{:.1}", momentum.mass));
+                row(ui, "Radius", format!("{radius:.2}"));
+                row(
+                    ui,
+                    "Closest approach",
+                    if closest_approach.is_finite() && closest_approach > 0.0 {
+                        format!("in {closest_approach:.1} s")
+                    } else {
+                        "receding".to_string()
+                    },
+                );
+                if let Some(intercept) = prediction.0 {
+                    row(ui, "Impact", format!("in {:.2} s", intercept.time));
+                }
+                // As hard as `fire_projectiles` makes it hit.
+                let impulse = weapon.hit_impulse(&config);
+                match weapon.kind {
+                    ProjectileKind::Mass => row(ui, "Per shot", format!("+{impulse:.1} mass")),
+                    _ => row(
+                        ui,
+                        "Δv per shot",
+                        format!("{:.3}", impulse * weapon.pellets as f32 / momentum.mass),
+                    ),
+                }
+            });
+        });
+}
+
+/// How fast time is running, buttons to change it, and the keys that do the same.
+pub fn time_readout(
+    mut ctx: EguiContexts,
+    mut clock: ResMut<SimulationClock>,
+    bindings: Res<InputBindings>,
+) {
+    let key = |action| {
+        bindings
+            .get(action)
+            .first()
+            .map_or("unbound".to_string(), |binding| binding.source.to_string())
+    };
+    let hint = if clock.is_stopped() {
+        format!(
+            "{} to step, {} to resume",
+            key(Action::StepTime),
+            key(Action::PauseTime)
+        )
+    } else {
+        format!(
+            "{} / {} for slower / faster",
+            key(Action::SlowDown),
+            key(Action::SpeedUp)
+        )
+    };
+    Window::new("Time")
+        .title_bar(false)
+        .anchor(Align2::CENTER_TOP, [0.0, 10.0])
+        .resizable(false)
+        .frame(Frame::window(&ctx.ctx_mut().style()).fill(Color32::from_black_alpha(160)))
+        .show(ctx.ctx_mut(), |ui| {
+            ui.horizontal(|ui| clock_controls(ui, &mut clock));
+            ui.label(RichText::new(hint).color(Color32::LIGHT_GREEN).monospace());
+        });
+}
+
+/// Pause, step, slower and faster, for the HUD and the inspector alike.
+pub fn clock_controls(ui: &mut Ui, clock: &mut SimulationClock) {
+    if clock.is_stopped() {
+        ui.label(RichText::new("Paused").color(Color32::YELLOW));
+        if ui.button("Resume").clicked() {
+            clock.paused = false;
+        }
+        if ui.button("Step").clicked() {
+            clock.step();
+        }
+    } else {
+        ui.label(RichText::new(clock.to_string()).color(Color32::GREEN));
+        if ui.button("Pause").clicked() {
+            clock.paused = true;
+        }
+    }
+    if ui.button("Slower").clicked() {
+        clock.slower();
+    }
+    if ui.button("Faster").clicked() {
+        clock.faster();
+    }
+}
+
+fn row(ui: &mut Ui, label: &str, value: String) {
+    ui.label(RichText::new(label).color(Color32::GREEN));
+    ui.label(RichText::new(value).color(Color32::LIGHT_GREEN).monospace());
+    ui.end_row();
+}
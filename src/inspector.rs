@@ -0,0 +1,328 @@
+use bevy::ecs::event::ManualEventReader;
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{self, Align2, CollapsingHeader, Color32, Grid, RichText, ScrollArea, Window},
+    EguiContexts,
+};
+use bevy_rapier3d::prelude::CollisionEvent;
+use std::collections::BTreeMap;
+
+use crate::bindings::{read_actions, Action, ActionState};
+use crate::clock::SimulationClock;
+use crate::craft::{
+    FireProjectileEvent, HotPlanetEvent, Projectile, ProjectileCollisionEvent, ProjectileTarget,
+};
+use crate::helpscreen::{grab_cursor, release_cursor};
+use crate::hud::clock_controls;
+use crate::particles::EmitParticles;
+use crate::physics::{
+    DeltaEvent, DespawnPlanetEvent, Momentum, PlanetCollisionEvent, PlanetMergeEvent,
+};
+use crate::scoring::{OwnershipChangedEvent, PlanetHitEvent, ScoreEvent};
+use crate::sound::PlaySound;
+use crate::AppState;
+
+const HIGHLIGHT_COLOR: Color = Color::YELLOW;
+
+#[derive(Resource, Default)]
+pub struct InspectorState {
+    pub open: bool,
+    pub selected: Option<Entity>,
+}
+
+/// How many of each event were sent last frame, by the event's name.
+#[derive(Resource, Default)]
+pub struct EventCounts(pub BTreeMap<&'static str, usize>);
+
+/// A window for poking at the game while it runs: what's in the world, what just happened,
+/// and a way to stop time to look at it. `Action::Inspector` (`F12`) opens and closes it.
+pub struct Inspector;
+
+impl Plugin for Inspector {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InspectorState>()
+            .init_resource::<EventCounts>()
+            .add_systems(
+                Update,
+                (
+                    toggle_inspector.after(read_actions),
+                    release_cursor
+                        .after(toggle_inspector)
+                        .run_if(inspector_toggled(true)),
+                    grab_cursor
+                        .after(toggle_inspector)
+                        .run_if(inspector_toggled(false).and_then(in_state(AppState::Playing))),
+                    inspector_panel.run_if(inspector_open),
+                    keep_clicks_on_windows.before(read_actions),
+                    highlight_selected.run_if(inspector_open),
+                ),
+            )
+            // Last, so every event sent this frame has been sent.
+            .add_systems(
+                Last,
+                (
+                    count_events::<DeltaEvent>,
+                    count_events::<PlanetCollisionEvent>,
+                    count_events::<PlanetMergeEvent>,
+                    count_events::<DespawnPlanetEvent>,
+                    count_events::<ProjectileCollisionEvent>,
+                    count_events::<HotPlanetEvent>,
+                    count_events::<FireProjectileEvent>,
+                    count_events::<PlanetHitEvent>,
+                    count_events::<OwnershipChangedEvent>,
+                    count_events::<ScoreEvent>,
+                    count_events::<EmitParticles>,
+                    count_events::<PlaySound>,
+                    count_events::<CollisionEvent>,
+                ),
+            );
+    }
+}
+
+fn inspector_open(inspector: Res<InspectorState>) -> bool {
+    inspector.open
+}
+
+// Whether the inspector has just been opened (or closed), as opposed to changed some other way.
+fn inspector_toggled(open: bool) -> impl FnMut(Res<InspectorState>, Local<bool>) -> bool {
+    move |inspector, mut was_open| {
+        let toggled = inspector.open != *was_open && inspector.open == open;
+        *was_open = inspector.open;
+        toggled
+    }
+}
+
+pub fn toggle_inspector(actions: Res<ActionState>, mut inspector: ResMut<InspectorState>) {
+    if actions.just_pressed(Action::Inspector) {
+        inspector.open = !inspector.open;
+    }
+}
+
+// Events no plugin has registered (rapier's, say, in a headless app) are left out.
+pub fn count_events<E: Event>(
+    events: Option<Res<Events<E>>>,
+    mut reader: Local<ManualEventReader<E>>,
+    mut counts: ResMut<EventCounts>,
+) {
+    let Some(events) = events else {
+        return;
+    };
+    let name = std::any::type_name::<E>().rsplit("::").next().unwrap();
+    counts.0.insert(name, reader.read(&events).count());
+}
+
+/// Clicking on the inspector (or the time controls) shouldn't also fire a projectile.
+pub fn keep_clicks_on_windows(
+    mut ctx: EguiContexts,
+    mut mouse_buttons: ResMut<Input<MouseButton>>,
+) {
+    if ctx.ctx_mut().is_pointer_over_area() {
+        mouse_buttons.reset_all();
+    }
+}
+
+type InspectedPlanets<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Transform,
+        &'static Momentum,
+        Option<&'static Children>,
+    ),
+>;
+
+type InspectedProjectiles<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Transform,
+        &'static Projectile,
+        Option<&'static ProjectileTarget>,
+    ),
+>;
+
+pub fn inspector_panel(
+    mut ctx: EguiContexts,
+    mut inspector: ResMut<InspectorState>,
+    counts: Res<EventCounts>,
+    mut clock: ResMut<SimulationClock>,
+    planet_query: InspectedPlanets,
+    projectile_query: InspectedProjectiles,
+) {
+    if let Some(selected) = inspector.selected {
+        if !planet_query.contains(selected) && !projectile_query.contains(selected) {
+            inspector.selected = None;
+        }
+    }
+    let mut selected = inspector.selected;
+
+    Window::new("Inspector")
+        .anchor(Align2::RIGHT_TOP, [-10.0, 10.0])
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                clock_controls(ui, &mut clock);
+                ui.label(format!("{:.2}s", clock.elapsed_seconds()));
+            });
+            ui.separator();
+
+            CollapsingHeader::new("Events last frame")
+                .default_open(true)
+                .show(ui, |ui| {
+                    Grid::new("events").striped(true).show(ui, |ui| {
+                        for (name, count) in counts.0.iter() {
+                            ui.label(*name);
+                            ui.label(count.to_string());
+                            ui.end_row();
+                        }
+                    });
+                });
+
+            CollapsingHeader::new(format!("Planets ({})", planet_query.iter().len()))
+                .default_open(true)
+                .show(ui, |ui| {
+                    ScrollArea::vertical()
+                        .id_source("planets")
+                        .max_height(200.0)
+                        .show(ui, |ui| {
+                            for (planet, _, momentum, _) in planet_query.iter() {
+                                let text = format!("{planet:?} mass {:.1}", momentum.mass);
+                                if ui
+                                    .selectable_label(selected == Some(planet), text)
+                                    .clicked()
+                                {
+                                    selected = Some(planet);
+                                }
+                            }
+                        });
+                });
+
+            CollapsingHeader::new(format!("Projectiles ({})", projectile_query.iter().len()))
+                .default_open(true)
+                .show(ui, |ui| {
+                    ScrollArea::vertical()
+                        .id_source("projectiles")
+                        .max_height(120.0)
+                        .show(ui, |ui| {
+                            for (projectile, _, state, target) in projectile_query.iter() {
+                                let text = match target {
+                                    Some(target) => {
+                                        format!(
+                                            "{projectile:?} {:?} at {:?}",
+                                            state.kind, target.planet
+                                        )
+                                    }
+                                    None => format!("{projectile:?} {:?}", state.kind),
+                                };
+                                if ui
+                                    .selectable_label(selected == Some(projectile), text)
+                                    .clicked()
+                                {
+                                    selected = Some(projectile);
+                                }
+                            }
+                        });
+                });
+            ui.separator();
+
+            match selected {
+                Some(entity) => {
+                    ui.heading(RichText::new(format!("{entity:?}")).color(Color32::GREEN));
+                    if let Ok((_, transform, momentum, children)) = planet_query.get(entity) {
+                        Grid::new("selected").show(ui, |ui| {
+                            transform_rows(ui, transform);
+                            ui.label("Mass");
+                            ui.label(format!("{:.3}", momentum.mass));
+                            ui.end_row();
+                            ui.label("Velocity");
+                            ui.label(vec3_text(momentum.velocity));
+                            ui.end_row();
+                            ui.label("Children");
+                            ui.label(match children {
+                                Some(children) => format!("{:?}", &children[..]),
+                                None => "none".to_string(),
+                            });
+                            ui.end_row();
+                        });
+                    } else if let Ok((_, transform, state, target)) = projectile_query.get(entity) {
+                        Grid::new("selected").show(ui, |ui| {
+                            transform_rows(ui, transform);
+                            ui.label("Kind");
+                            ui.label(format!("{:?}", state.kind));
+                            ui.end_row();
+                            ui.label("Velocity");
+                            ui.label(vec3_text(state.velocity));
+                            ui.end_row();
+                            ui.label("Impulse");
+                            ui.label(format!("{:.3}", state.impulse));
+                            ui.end_row();
+                            ui.label("Target");
+                            ui.label(match target {
+                                Some(target) => format!(
+                                    "{:?} toward {}",
+                                    target.planet,
+                                    vec3_text(target.local_direction)
+                                ),
+                                None => "none".to_string(),
+                            });
+                            ui.end_row();
+                        });
+                    }
+                    if ui.button("Deselect").clicked() {
+                        selected = None;
+                    }
+                }
+                None => {
+                    ui.label("Select a planet or projectile to highlight it.");
+                }
+            }
+        });
+
+    if selected != inspector.selected {
+        inspector.selected = selected;
+    }
+}
+
+fn transform_rows(ui: &mut egui::Ui, transform: &Transform) {
+    ui.label("Translation");
+    ui.label(vec3_text(transform.translation));
+    ui.end_row();
+    ui.label("Scale");
+    ui.label(vec3_text(transform.scale));
+    ui.end_row();
+}
+
+fn vec3_text(v: Vec3) -> String {
+    format!("({:.2}, {:.2}, {:.2})", v.x, v.y, v.z)
+}
+
+/// Rings the selected entity, and draws a line from a homing projectile to its target.
+pub fn highlight_selected(
+    inspector: Res<InspectorState>,
+    transform_query: Query<&Transform>,
+    target_query: Query<&ProjectileTarget>,
+    mut gizmos: Gizmos,
+) {
+    let Some(selected) = inspector.selected else {
+        return;
+    };
+    let Ok(transform) = transform_query.get(selected) else {
+        return;
+    };
+    // Planets are scaled to their radius. Projectiles are small, but we still want to see them.
+    let radius = transform.scale.max_element().max(0.5) * 1.2;
+    gizmos.sphere(
+        transform.translation,
+        Quat::IDENTITY,
+        radius,
+        HIGHLIGHT_COLOR,
+    );
+    if let Ok(target) = target_query.get(selected) {
+        if let Ok(planet) = transform_query.get(target.planet) {
+            gizmos.line(transform.translation, planet.translation, HIGHLIGHT_COLOR);
+        }
+    }
+}
@@ -1,15 +1,35 @@
-use bevy::app::PluginGroupBuilder;
+use bevy::app::{PluginGroupBuilder, ScheduleRunnerPlugin};
 
+use bevy::asset::load_internal_asset;
 use bevy::prelude::*;
+use bevy::sprite::Material2dPlugin;
 use bevy_egui::EguiPlugin;
 use bevy_rapier3d::prelude::{NoUserData, RapierConfiguration, RapierPhysicsPlugin};
 use rand::Rng;
 use std::f32::consts::{PI, TAU};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
 
+mod appearance;
+mod bindings;
+mod clock;
 mod craft;
 mod helpscreen;
+mod hud;
+mod inspector;
+mod networking;
+mod noise;
+mod particles;
 mod physics;
 pub mod prelude;
+mod radar;
+mod rewind;
+mod round;
+mod scoring;
+mod settings;
+mod sky;
+mod sound;
 
 use prelude::*;
 
@@ -21,6 +41,26 @@ impl PluginGroup for FullGame {
             .add(Core)
             .add(SpacecraftPlugin)
             .add(Spacetime)
+            .add(Scoring)
+            .add(Rounds)
+            .add(Sound)
+            .add(Inspector)
+            .add(Rewinding)
+    }
+}
+
+/// Runs the shared planet system for networked clients, without a window.
+pub struct DedicatedServer {
+    pub bind: SocketAddr,
+}
+
+impl PluginGroup for DedicatedServer {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add(HeadlessCore)
+            .add(Spacetime)
+            .add(Scoring)
+            .add(ServerPlugin { bind: self.bind })
     }
 }
 
@@ -28,30 +68,139 @@ pub struct SpacecraftPlugin;
 
 impl Plugin for SpacecraftPlugin {
     fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            ANAGLYPH_SHADER_HANDLE,
+            "craft/anaglyph.wgsl",
+            Shader::from_wgsl
+        );
         app.init_resource::<SpacecraftConfig>()
+            .init_resource::<RadarConfig>()
+            .init_resource::<InterceptPrediction>()
+            .init_resource::<LockedTarget>()
+            .init_resource::<CameraConfig>()
+            .init_resource::<SkyConfig>()
+            .init_resource::<AppearanceConfig>()
+            .init_resource::<ParticleConfig>()
+            .add_plugins(Material2dPlugin::<AnaglyphMaterial>::default())
+            .insert_resource(InputBindings::load())
+            .init_resource::<ActionState>()
+            .init_resource::<RebindingPage>()
+            .init_resource::<GraphicsQuality>()
+            .init_resource::<SettingsPage>()
             .add_event::<ProjectileCollisionEvent>()
             .add_event::<HotPlanetEvent>()
             .add_event::<FireProjectileEvent>()
+            .add_event::<EmitParticles>()
             .add_systems(
                 Update,
                 (
-                    move_forward,
+                    engage_autopilot
+                        .after(signal_hot_planet)
+                        .after(read_actions),
+                    autopilot.after(control).after(engage_autopilot),
+                    fly.after(autopilot),
+                    control_clock.after(read_actions),
                     control,
-                    stars,
                     signal_hot_planet,
-                    fire_on_hot_planet,
-                    animate_projectile_explosion,
+                    lock_target.after(signal_hot_planet).after(read_actions),
+                    fire_projectiles.after(cool_weapons).after(switch_weapon),
+                    shot_feedback.before(fire_projectiles),
+                    cool_weapons,
+                    switch_weapon.after(read_actions),
                     handle_hot_planet,
                     set_ar_default_visibility.before(handle_hot_planet),
                     move_projectiles.before(handle_despawn_planet),
                     transfer_projectile_momentum,
-                    spawn_projectile_explosion_animation.after(handle_despawn_planet),
-                    handle_projectile_despawn.after(spawn_projectile_explosion_animation),
+                    handle_projectile_despawn,
                 )
                     .run_if(in_state(AppState::Playing)),
             )
-            .add_systems(Startup, spacecraft_setup)
-            .add_systems(Update, helpscreen.run_if(in_state(AppState::Help)));
+            .add_systems(
+                Startup,
+                (
+                    load_settings.before(camera_rig_setup),
+                    spacecraft_setup,
+                    camera_rig_setup,
+                    stereo_setup,
+                    intercept_marker_setup,
+                    particle_pool_setup,
+                    shockwave_setup,
+                    shot_feedback_setup,
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    switch_camera_mode
+                        .after(read_actions)
+                        .run_if(in_state(AppState::Playing)),
+                    fly_cinematic_camera
+                        .after(switch_camera_mode)
+                        .run_if(in_state(AppState::Playing)),
+                    move_camera_rig.after(fly_cinematic_camera).after(fly),
+                    show_hull.after(move_camera_rig),
+                    adjust_stereo
+                        .after(read_actions)
+                        .run_if(in_state(AppState::Playing)),
+                    apply_stereo.after(adjust_stereo),
+                ),
+            )
+            .add_systems(Update, (paint_planets, drift_clouds, show_planet_owner))
+            .add_systems(
+                Update,
+                (
+                    impact_particles.before(emit_particles),
+                    merge_particles
+                        .after(transfer_planet_momentum)
+                        .before(emit_particles),
+                    exhaust_particles.after(fly).before(emit_particles),
+                    emit_particles,
+                    update_particles.after(emit_particles),
+                    spawn_shockwaves.after(transfer_planet_momentum),
+                    expand_shockwaves,
+                    record_trails.after(handle_freefall),
+                    draw_trails.after(record_trails),
+                ),
+            )
+            .add_systems(Update, helpscreen.run_if(in_state(AppState::Help)))
+            // Not just while playing: the help key has to close the help screen too.
+            .add_systems(Update, read_actions.before(control))
+            .add_systems(
+                Update,
+                (
+                    pause_on_help_key.run_if(in_state(AppState::Playing)),
+                    resume_on_help_key.run_if(in_state(AppState::Help)),
+                )
+                    .after(read_actions),
+            )
+            .add_systems(
+                Update,
+                (capture_binding, rebinding_page.after(capture_binding))
+                    .run_if(in_state(AppState::Controls)),
+            )
+            .add_systems(OnEnter(AppState::Controls), pause_time)
+            .add_systems(OnExit(AppState::Controls), resume_time)
+            .add_systems(Update, settings_page.run_if(in_state(AppState::Settings)))
+            .add_systems(OnEnter(AppState::Settings), pause_time)
+            .add_systems(OnExit(AppState::Settings), resume_time)
+            .add_systems(OnEnter(AppState::Help), pause_time)
+            .add_systems(OnExit(AppState::Help), (resume_time, swallow_clicks))
+            .add_systems(OnEnter(AppState::Playing), grab_cursor)
+            .add_systems(OnExit(AppState::Playing), release_cursor)
+            .add_systems(Update, scoreboard.run_if(in_state(AppState::Playing)))
+            .add_systems(
+                Update,
+                (
+                    predict_intercept.after(signal_hot_planet),
+                    show_intercept_marker.after(predict_intercept),
+                    hud.after(predict_intercept),
+                    time_readout,
+                    radar,
+                    offscreen_indicators,
+                )
+                    .run_if(in_state(AppState::Playing)),
+            );
     }
 }
 
@@ -63,14 +212,20 @@ impl Plugin for Spacetime {
             .add_event::<DeltaEvent>()
             .add_event::<PlanetCollisionEvent>()
             .add_event::<DespawnPlanetEvent>()
+            .add_event::<PlanetMergeEvent>()
             .add_systems(
                 Update,
                 (
                     handle_despawn_planet,
                     signal_freefall_delta.before(handle_despawn_planet),
                     handle_freefall.before(handle_despawn_planet),
+                    size_planets
+                        .after(handle_freefall)
+                        .after(transfer_planet_momentum),
                     handle_planet_collisions.before(handle_despawn_planet),
-                    transfer_planet_momentum.before(handle_despawn_planet),
+                    transfer_planet_momentum
+                        .before(handle_despawn_planet)
+                        .run_if(is_local_authority),
                 )
                     .run_if(in_state(AppState::Playing)),
             );
@@ -78,29 +233,82 @@ impl Plugin for Spacetime {
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Copy, Default, States)]
-enum AppState {
+pub enum AppState {
     #[default]
+    MainMenu,
+    Countdown,
     Playing,
     Help,
+    Controls,
+    Settings,
+    Replay,
+    RoundOver,
+    Results,
 }
 
 pub struct Core;
 
 impl Plugin for Core {
     fn build(&self, app: &mut App) {
-        app.add_plugins(DefaultPlugins.set(bevy::log::LogPlugin {
-                      filter: "info,wgpu_core=warn,wgpu_hal=off,mass_gathering=debug,mass_gathering::networking=debug".into(),
-                            level: bevy::log::Level::DEBUG,
-                       }));
+        app.add_plugins(DefaultPlugins.set(log_plugin()));
         app.add_systems(Update, bevy::window::close_on_esc);
         app.add_plugins(EguiPlugin)
             .add_state::<AppState>()
+            .init_resource::<SimulationClock>()
             .add_systems(Startup, disable_rapier_gravity)
+            .add_systems(PreUpdate, tick_simulation_clock)
             .add_systems(Update, timer_despawn)
             .add_plugins(RapierPhysicsPlugin::<NoUserData>::default());
     }
 }
 
+/// Where user settings are kept: `$XDG_CONFIG_HOME/mass_gathering`, `~/.config/mass_gathering`
+/// or `%APPDATA%\mass_gathering`, falling back to the working directory.
+pub fn config_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+        .unwrap_or_default();
+    base.join("mass_gathering")
+}
+
+/// Everything `Core` provides that still makes sense without a window or GPU.
+pub struct HeadlessCore;
+
+impl Plugin for HeadlessCore {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(
+            Duration::from_secs_f64(1.0 / 60.0),
+        )))
+        .add_plugins((
+            log_plugin(),
+            TransformPlugin,
+            HierarchyPlugin,
+            AssetPlugin::default(),
+            bevy::scene::ScenePlugin,
+        ))
+        // Planets are spawned with a mesh and material even when nobody looks at them.
+        .init_asset::<Mesh>()
+        .init_asset::<StandardMaterial>()
+        .add_state::<AppState>()
+        .init_resource::<SimulationClock>()
+        .add_systems(Startup, disable_rapier_gravity)
+        .add_systems(PreUpdate, tick_simulation_clock)
+        .add_systems(Update, timer_despawn)
+        .add_plugins(RapierPhysicsPlugin::<NoUserData>::default());
+    }
+}
+
+fn log_plugin() -> bevy::log::LogPlugin {
+    bevy::log::LogPlugin {
+        filter:
+            "info,wgpu_core=warn,wgpu_hal=off,mass_gathering=debug,mass_gathering::networking=debug"
+                .into(),
+        level: bevy::log::Level::DEBUG,
+    }
+}
+
 fn disable_rapier_gravity(mut rapier_config: ResMut<RapierConfiguration>) {
     rapier_config.gravity = Vec3::ZERO;
 }
@@ -116,29 +324,37 @@ fn latlon_to_cartesian(lat: f32, lon: f32) -> Vec3 {
     Vec3::new(x, y, z)
 }
 
+/// The same for every planet, so a planet's size follows from its mass alone.
+pub const DENSITY: f32 = 1.0;
+
 pub(crate) fn radius_to_mass(radius: f32) -> f32 {
-    (4.0 / 3.0) * PI * radius.powf(3.0)
+    DENSITY * (4.0 / 3.0) * PI * radius.powf(3.0)
 }
 
 pub(crate) fn mass_to_radius(mass: f32) -> f32 {
-    ((mass * (3.0 / 4.0)) / PI).powf(1.0 / 3.0)
+    ((mass / DENSITY * (3.0 / 4.0)) / PI).powf(1.0 / 3.0)
 }
 
-#[derive(Component)]
-pub struct Star;
-
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct DespawnTimer {
     pub ttl: Timer,
+    /// Counts down on the `SimulationClock`, rather than in real time.
+    pub simulated: bool,
 }
 
 pub fn timer_despawn(
     mut commands: Commands,
     mut despawn_query: Query<(Entity, &mut DespawnTimer)>,
     time: Res<Time>,
+    clock: Res<SimulationClock>,
 ) {
     for (entity, mut despawn_timer) in despawn_query.iter_mut() {
-        despawn_timer.ttl.tick(time.delta());
+        let delta = if despawn_timer.simulated {
+            clock.delta()
+        } else {
+            time.delta()
+        };
+        despawn_timer.ttl.tick(delta);
         if despawn_timer.ttl.finished() {
             debug!("Despawning by timer: {entity:?}");
             commands.entity(entity).despawn();
@@ -159,55 +375,16 @@ pub fn my_planets(
         let velocity = latlon_to_cartesian(rf(), rf()) * Vec3::new(10.0, rf() * 0.1, 10.0) * 0.1;
         let radius = rf() + 2.0;
         for side in [-1.0, 1.0] {
-            let color = Color::rgb(rf(), rf(), rf());
+            let appearance = Appearance::random(&mut rand::thread_rng());
             spawn_planet(
                 radius,
                 position * side,
                 velocity * side,
-                color,
+                appearance,
                 &mut commands,
                 &mut meshes,
                 &mut materials,
             );
         }
     }
-
-    // poorly implemented stars!!
-    let star_count = 40;
-    for _ in 0..star_count {
-        let position = latlon_to_cartesian(rf(), rf()) * 400.0;
-        let radius = rf() * 0.3 + 0.7;
-        let (r, w, y) = (rf() * 40.0, rf() * 400.0, rf() * 20.0);
-        let star_colored = (Color::RED * r + Color::WHITE * w + Color::YELLOW * y) * 1000.0;
-        commands
-            .spawn(PbrBundle {
-                mesh: meshes.add(
-                    Mesh::try_from(shape::Icosphere {
-                        radius,
-                        ..default()
-                    })
-                    .unwrap(),
-                ),
-                material: materials.add(star_colored.into()),
-                transform: Transform::from_translation(position),
-                ..default()
-            })
-            .insert(Star);
-    }
-}
-
-#[derive(Default)]
-pub struct Prev(pub Vec3);
-
-fn stars(
-    mut stars_query: Query<&mut Transform, (With<Star>, Without<Spacecraft>)>,
-
-    spacecraft_query: Query<&mut Transform, With<Spacecraft>>,
-    mut previous: Local<Prev>,
-) {
-    let spacecraft = spacecraft_query.get_single().unwrap();
-    for mut star in stars_query.iter_mut() {
-        star.translation += spacecraft.translation - previous.0;
-    }
-    previous.0 = spacecraft.translation;
 }
@@ -1,23 +1,38 @@
-use bevy::prelude::{App, ClearColor, Color, Startup, Transform, Vec3};
-use mass_gathering::prelude::{my_planets, PhysicsConfig, SpacecraftConfig};
-use mass_gathering::FullGame;
+use bevy::prelude::{App, ClearColor, Color, OnEnter, Transform, Vec3};
+use mass_gathering::prelude::{my_planets, ClientPlugin, PhysicsConfig, SpacecraftConfig};
+use mass_gathering::{AppState, FullGame};
+use std::net::SocketAddr;
 
 fn main() {
     let d = 60.0 / 3.0_f32.powf(0.5); // about right for my_planets
-    App::new()
-        .insert_resource(ClearColor(Color::MIDNIGHT_BLUE * 0.1))
+    let mut app = App::new();
+    app.insert_resource(ClearColor(Color::MIDNIGHT_BLUE * 0.1))
         .insert_resource(PhysicsConfig {
             sims_per_frame: 1,
             trails: true,
             trail_ttl: 2500 * 5,
+            ..Default::default()
         })
         .insert_resource(SpacecraftConfig {
-            stereo_enabled: false,
             start_transform: Transform::from_xyz(d, d, d).looking_at(Vec3::ZERO, Vec3::Y),
-            impact_magnitude: 5.0,
             ..Default::default()
         })
-        .add_plugins(FullGame)
-        .add_systems(Startup, my_planets)
-        .run();
+        .add_plugins(FullGame);
+    // `--connect <address>` joins a dedicated server, which then provides the planets.
+    match server_address() {
+        Some(server) => app.add_plugins(ClientPlugin { server }),
+        None => app.add_systems(OnEnter(AppState::Countdown), my_planets),
+    };
+    app.run();
+}
+
+fn server_address() -> Option<SocketAddr> {
+    std::env::args()
+        .skip_while(|arg| arg != "--connect")
+        .nth(1)
+        .map(|address| {
+            address
+                .parse()
+                .expect("Expected a server address like 127.0.0.1:5151")
+        })
 }
@@ -0,0 +1,340 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::Duration;
+
+use super::{ClientMessage, NetId, ServerMessage, Snapshot, MAX_PACKET_SIZE};
+use crate::appearance::Appearance;
+use crate::craft::{ProjectileCollisionEvent, Spacecraft};
+use crate::physics::{
+    animate_growth, spawn_planet, DespawnPlanetEvent, Momentum, PlanetMergeEvent,
+};
+use crate::scoring::{LocalPlayer, Owner, OwnershipChangedEvent, Player, Score};
+use crate::{mass_to_radius, AppState};
+
+// Repeat `Hello` this often until the server answers.
+const HELLO_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct ClientPlugin {
+    pub server: SocketAddr,
+}
+
+impl Plugin for ClientPlugin {
+    fn build(&self, app: &mut App) {
+        let local: SocketAddr = if self.server.is_ipv4() {
+            (Ipv4Addr::UNSPECIFIED, 0).into()
+        } else {
+            (Ipv6Addr::UNSPECIFIED, 0).into()
+        };
+        let socket = UdpSocket::bind(local).expect("Could not bind client socket");
+        socket
+            .connect(self.server)
+            .expect("Could not connect client socket");
+        socket
+            .set_nonblocking(true)
+            .expect("Could not make client socket non-blocking");
+        info!("Connecting to server at {}", self.server);
+        // Rounds are a local affair. Networked play goes straight to the action.
+        app.insert_resource(NextState(Some(AppState::Playing)))
+            .insert_resource(ClientSocket {
+                socket,
+                client_id: None,
+                last_tick: 0,
+                snapshot: None,
+                planets: HashMap::new(),
+                crafts: HashMap::new(),
+            })
+            .add_systems(
+                Update,
+                (
+                    client_hello,
+                    client_receive,
+                    (
+                        client_apply_planets,
+                        client_apply_crafts,
+                        client_apply_scores,
+                    )
+                        .after(client_receive),
+                    (client_send_craft, client_send_impacts).run_if(in_state(AppState::Playing)),
+                ),
+            )
+            .add_systems(Last, client_goodbye);
+    }
+}
+
+#[derive(Resource)]
+pub struct ClientSocket {
+    socket: UdpSocket,
+    pub client_id: Option<u32>,
+    last_tick: u64,
+    // The newest snapshot, if one arrived this frame.
+    snapshot: Option<Snapshot>,
+    // Server ids of everything we mirror, and the local entities that stand in for them.
+    planets: HashMap<u32, Entity>,
+    crafts: HashMap<u32, Entity>,
+}
+
+/// Another player's spacecraft, as last reported by the server.
+#[derive(Component)]
+pub struct RemoteSpacecraft {
+    pub client_id: u32,
+}
+
+impl ClientSocket {
+    fn send(&self, message: &ClientMessage) {
+        if let Err(error) = self.socket.send(&message.encode()) {
+            warn!("While sending to server: {error}");
+        }
+    }
+}
+
+pub fn client_hello(client: Res<ClientSocket>, time: Res<Time>, mut since: Local<Duration>) {
+    if client.client_id.is_some() {
+        return;
+    }
+    *since += time.delta();
+    if *since >= HELLO_INTERVAL || time.elapsed_seconds() == 0.0 {
+        *since = Duration::ZERO;
+        client.send(&ClientMessage::Hello);
+    }
+}
+
+pub fn client_receive(mut client: ResMut<ClientSocket>) {
+    let mut buffer = [0; MAX_PACKET_SIZE];
+    client.snapshot = None;
+    loop {
+        let length = match client.socket.recv(&mut buffer) {
+            Ok(length) => length,
+            Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+            Err(error) => {
+                warn!("While receiving from server: {error}");
+                break;
+            }
+        };
+        match ServerMessage::decode(&buffer[..length]) {
+            Some(ServerMessage::Welcome { client_id }) => {
+                if client.client_id.is_none() {
+                    info!("Joined the server as client {client_id}");
+                }
+                client.client_id = Some(client_id);
+            }
+            Some(ServerMessage::Snapshot(snapshot)) => {
+                // Datagrams may arrive out of order. Only the newest snapshot matters.
+                if snapshot.tick > client.last_tick {
+                    client.last_tick = snapshot.tick;
+                    client.snapshot = Some(snapshot);
+                }
+            }
+            None => warn!("Discarding malformed packet from server"),
+        }
+    }
+}
+
+/// What happened to the planets, as though it had happened here.
+#[derive(SystemParam)]
+pub struct MirroredEvents<'w> {
+    despawn: EventWriter<'w, DespawnPlanetEvent>,
+    ownership: EventWriter<'w, OwnershipChangedEvent>,
+    merge: EventWriter<'w, PlanetMergeEvent>,
+}
+
+pub fn client_apply_planets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut client: ResMut<ClientSocket>,
+    mut planet_query: Query<(&mut Transform, &mut Momentum, &mut Owner, &mut Appearance)>,
+    mut events: MirroredEvents,
+) {
+    let client = &mut *client;
+    let Some(snapshot) = client.snapshot.as_ref() else {
+        return;
+    };
+    let count = client.planets.len();
+    // Where the planets that have gone were, and how big they were.
+    let mut gone = Vec::new();
+    client.planets.retain(|id, &mut entity| {
+        let present = snapshot.planets.iter().any(|planet| planet.id == *id);
+        if !present {
+            debug!("Server no longer has planet {id}, despawning {entity:?}");
+            if let Ok((transform, momentum, ..)) = planet_query.get(entity) {
+                gone.push((entity, transform.translation, momentum.mass));
+            }
+            events.despawn.send(DespawnPlanetEvent(entity));
+        }
+        present
+    });
+    // The server doesn't say what merged with what, but a planet that grows just as another goes
+    // has surely swallowed it.
+    let merged = client.planets.len() < count;
+    let mut grown = Vec::new();
+    for planet in snapshot.planets.iter() {
+        let owner = planet.owner.map(Player);
+        match client.planets.get(&planet.id) {
+            Some(&entity) => {
+                if let Ok((mut transform, mut momentum, mut planet_owner, mut appearance)) =
+                    planet_query.get_mut(entity)
+                {
+                    // Only changes in a merge. Repainting is not cheap, so don't trigger it otherwise.
+                    appearance.set_if_neq(planet.appearance);
+                    transform.translation = planet.position;
+                    if merged && planet.mass > momentum.mass {
+                        animate_growth(&mut commands, entity, &transform);
+                        grown.push((entity, transform.translation, momentum.mass, owner));
+                    }
+                    momentum.velocity = planet.velocity;
+                    momentum.mass = planet.mass;
+                    if planet_owner.player != owner {
+                        events.ownership.send(OwnershipChangedEvent {
+                            planet: entity,
+                            previous: planet_owner.player,
+                            owner,
+                        });
+                        planet_owner.player = owner;
+                    }
+                }
+            }
+            None => {
+                let entity = spawn_planet(
+                    mass_to_radius(planet.mass),
+                    planet.position,
+                    planet.velocity,
+                    planet.appearance,
+                    &mut commands,
+                    &mut meshes,
+                    &mut materials,
+                );
+                commands.entity(entity).insert((
+                    NetId(planet.id),
+                    Owner {
+                        player: owner,
+                        ..default()
+                    },
+                ));
+                client.planets.insert(planet.id, entity);
+            }
+        }
+    }
+    // Each planet that went was swallowed by whichever of the planets that grew was nearest.
+    for (minor, position, mass) in gone {
+        let Some(&(major, major_position, major_mass, owner)) = grown.iter().min_by(|a, b| {
+            a.1.distance_squared(position)
+                .total_cmp(&b.1.distance_squared(position))
+        }) else {
+            continue;
+        };
+        events.merge.send(PlanetMergeEvent {
+            major,
+            minor,
+            absorbed_mass: mass,
+            position: major_position
+                + (position - major_position).normalize_or_zero() * mass_to_radius(major_mass),
+            owner,
+        });
+    }
+}
+
+pub fn client_apply_crafts(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut client: ResMut<ClientSocket>,
+    mut craft_query: Query<&mut Transform, With<RemoteSpacecraft>>,
+) {
+    let client = &mut *client;
+    let Some(snapshot) = client.snapshot.as_ref() else {
+        return;
+    };
+    client.crafts.retain(|id, &mut entity| {
+        let present = snapshot.crafts.iter().any(|craft| craft.client_id == *id);
+        if !present {
+            info!("Client {id} left");
+            commands.entity(entity).despawn_recursive();
+        }
+        present
+    });
+    for craft in snapshot.crafts.iter() {
+        let transform =
+            Transform::from_translation(craft.translation).with_rotation(craft.rotation);
+        match client.crafts.get(&craft.client_id) {
+            Some(&entity) => {
+                if let Ok(mut craft_transform) = craft_query.get_mut(entity) {
+                    *craft_transform = transform;
+                }
+            }
+            None => {
+                info!("Client {} joined", craft.client_id);
+                let entity = commands
+                    .spawn(PbrBundle {
+                        mesh: meshes.add(Mesh::from(shape::Box::new(0.6, 0.2, 1.2))),
+                        material: materials.add(Color::ORANGE_RED.into()),
+                        transform,
+                        ..default()
+                    })
+                    .insert(RemoteSpacecraft {
+                        client_id: craft.client_id,
+                    })
+                    .id();
+                client.crafts.insert(craft.client_id, entity);
+            }
+        }
+    }
+}
+
+/// The server keeps score. We just display it.
+pub fn client_apply_scores(
+    client: Res<ClientSocket>,
+    mut score: ResMut<Score>,
+    mut local_player: ResMut<LocalPlayer>,
+) {
+    if let Some(client_id) = client.client_id {
+        local_player.set_if_neq(LocalPlayer(Player(client_id)));
+    }
+    if let Some(snapshot) = client.snapshot.as_ref() {
+        score.points = snapshot
+            .scores
+            .iter()
+            .map(|&(player, points)| (Player(player), points))
+            .collect();
+    }
+}
+
+pub fn client_send_craft(
+    client: Res<ClientSocket>,
+    spacecraft_query: Query<&Transform, With<Spacecraft>>,
+) {
+    if client.client_id.is_none() {
+        return;
+    }
+    for transform in spacecraft_query.iter() {
+        client.send(&ClientMessage::Craft {
+            translation: transform.translation,
+            rotation: transform.rotation,
+        });
+    }
+}
+
+/// Our own hits are applied locally right away and also reported, so the server can apply them for real.
+pub fn client_send_impacts(
+    client: Res<ClientSocket>,
+    planet_query: Query<&NetId>,
+    mut projectile_events: EventReader<ProjectileCollisionEvent>,
+) {
+    for event in projectile_events.read() {
+        if let Ok(&NetId(planet)) = planet_query.get(event.planet) {
+            client.send(&ClientMessage::Impact {
+                planet,
+                local_direction: event.local_impact_site.normalize(),
+                kind: event.kind,
+            });
+        }
+    }
+}
+
+pub fn client_goodbye(client: Res<ClientSocket>, mut exit_events: EventReader<bevy::app::AppExit>) {
+    if exit_events.read().next().is_some() && client.client_id.is_some() {
+        client.send(&ClientMessage::Goodbye);
+    }
+}
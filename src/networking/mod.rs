@@ -0,0 +1,533 @@
+use bevy::prelude::*;
+
+use crate::appearance::{Appearance, Composition};
+use crate::craft::ProjectileKind;
+
+mod client;
+mod server;
+pub use client::*;
+pub use server::*;
+
+/// Port used by the dedicated server when none is given.
+pub const DEFAULT_PORT: u16 = 5151;
+
+// Comfortably below the largest UDP datagram (65507 bytes).
+const MAX_PACKET_SIZE: usize = 60_000;
+
+/// Server-assigned identity of a planet, shared by the server and every client.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NetId(pub u32);
+
+/// The server is the authority on planet merges. A client only mirrors them.
+pub fn is_local_authority(client: Option<Res<ClientSocket>>) -> bool {
+    client.is_none()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanetState {
+    pub id: u32,
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub mass: f32,
+    pub appearance: Appearance,
+    pub owner: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CraftState {
+    pub client_id: u32,
+    pub translation: Vec3,
+    pub rotation: Quat,
+}
+
+// Every snapshot is complete: planets (or crafts) missing from it are gone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub tick: u64,
+    pub planets: Vec<PlanetState>,
+    pub crafts: Vec<CraftState>,
+    pub scores: Vec<(u32, u32)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientMessage {
+    Hello,
+    Goodbye,
+    Craft {
+        translation: Vec3,
+        rotation: Quat,
+    },
+    // A projectile fired by this client struck `planet`.
+    Impact {
+        planet: u32,
+        local_direction: Vec3,
+        kind: ProjectileKind,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerMessage {
+    Welcome { client_id: u32 },
+    Snapshot(Snapshot),
+}
+
+impl ClientMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::default();
+        match self {
+            ClientMessage::Hello => writer.u8(0),
+            ClientMessage::Goodbye => writer.u8(1),
+            ClientMessage::Craft {
+                translation,
+                rotation,
+            } => {
+                writer.u8(2);
+                writer.vec3(*translation);
+                writer.quat(*rotation);
+            }
+            ClientMessage::Impact {
+                planet,
+                local_direction,
+                kind,
+            } => {
+                writer.u8(3);
+                writer.u32(*planet);
+                writer.vec3(*local_direction);
+                writer.projectile_kind(*kind);
+            }
+        }
+        writer.0
+    }
+
+    /// Whether the numbers in the message make sense. A well-formed packet can still carry a zero
+    /// direction, or a NaN that would spread to every planet it touches.
+    pub fn is_plausible(&self) -> bool {
+        match self {
+            ClientMessage::Hello | ClientMessage::Goodbye => true,
+            ClientMessage::Craft {
+                translation,
+                rotation,
+            } => translation.is_finite() && rotation.is_finite() && rotation.length() > 0.5,
+            ClientMessage::Impact {
+                local_direction, ..
+            } => local_direction.is_finite() && local_direction.length() > 0.5,
+        }
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader::new(bytes);
+        let message = match reader.u8()? {
+            0 => ClientMessage::Hello,
+            1 => ClientMessage::Goodbye,
+            2 => ClientMessage::Craft {
+                translation: reader.vec3()?,
+                rotation: reader.quat()?,
+            },
+            3 => ClientMessage::Impact {
+                planet: reader.u32()?,
+                local_direction: reader.vec3()?,
+                kind: reader.projectile_kind()?,
+            },
+            _ => return None,
+        };
+        reader.finish(message)
+    }
+}
+
+impl ServerMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::default();
+        match self {
+            ServerMessage::Welcome { client_id } => {
+                writer.u8(0);
+                writer.u32(*client_id);
+            }
+            ServerMessage::Snapshot(Snapshot {
+                tick,
+                planets,
+                crafts,
+                scores,
+            }) => {
+                writer.u8(1);
+                writer.u64(*tick);
+                writer.u32(planets.len() as u32);
+                for planet in planets {
+                    writer.u32(planet.id);
+                    writer.vec3(planet.position);
+                    writer.vec3(planet.velocity);
+                    writer.f32(planet.mass);
+                    writer.appearance(&planet.appearance);
+                    writer.option_u32(planet.owner);
+                }
+                writer.u32(crafts.len() as u32);
+                for craft in crafts {
+                    writer.u32(craft.client_id);
+                    writer.vec3(craft.translation);
+                    writer.quat(craft.rotation);
+                }
+                writer.u32(scores.len() as u32);
+                for (player, points) in scores {
+                    writer.u32(*player);
+                    writer.u32(*points);
+                }
+            }
+        }
+        writer.0
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader::new(bytes);
+        let message = match reader.u8()? {
+            0 => ServerMessage::Welcome {
+                client_id: reader.u32()?,
+            },
+            1 => {
+                let tick = reader.u64()?;
+                let mut planets = vec![];
+                for _ in 0..reader.u32()? {
+                    planets.push(PlanetState {
+                        id: reader.u32()?,
+                        position: reader.vec3()?,
+                        velocity: reader.vec3()?,
+                        mass: reader.f32()?,
+                        appearance: reader.appearance()?,
+                        owner: reader.option_u32()?,
+                    });
+                }
+                let mut crafts = vec![];
+                for _ in 0..reader.u32()? {
+                    crafts.push(CraftState {
+                        client_id: reader.u32()?,
+                        translation: reader.vec3()?,
+                        rotation: reader.quat()?,
+                    });
+                }
+                let mut scores = vec![];
+                for _ in 0..reader.u32()? {
+                    scores.push((reader.u32()?, reader.u32()?));
+                }
+                ServerMessage::Snapshot(Snapshot {
+                    tick,
+                    planets,
+                    crafts,
+                    scores,
+                })
+            }
+            _ => return None,
+        };
+        reader.finish(message)
+    }
+}
+
+// Everything goes over the wire little-endian, with no framing beyond the datagram itself.
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn option_u32(&mut self, value: Option<u32>) {
+        match value {
+            Some(value) => {
+                self.u8(1);
+                self.u32(value);
+            }
+            None => self.u8(0),
+        }
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn vec3(&mut self, value: Vec3) {
+        for component in value.to_array() {
+            self.f32(component);
+        }
+    }
+
+    fn quat(&mut self, value: Quat) {
+        for component in value.to_array() {
+            self.f32(component);
+        }
+    }
+
+    fn projectile_kind(&mut self, kind: ProjectileKind) {
+        self.u8(match kind {
+            ProjectileKind::Push => 0,
+            ProjectileKind::Pull => 1,
+            ProjectileKind::Mass => 2,
+            ProjectileKind::Spread => 3,
+        });
+    }
+
+    fn appearance(&mut self, appearance: &Appearance) {
+        let Composition { rock, water, gas } = appearance.composition;
+        let [r, g, b, _] = appearance.tint.as_rgba_f32();
+        self.u64(appearance.seed);
+        for value in [rock, water, gas, r, g, b] {
+            self.f32(value);
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        if self.bytes.len() < N {
+            return None;
+        }
+        let (head, tail) = self.bytes.split_at(N);
+        self.bytes = tail;
+        head.try_into().ok()
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take::<1>().map(|[value]| value)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take().map(u64::from_le_bytes)
+    }
+
+    fn option_u32(&mut self) -> Option<Option<u32>> {
+        match self.u8()? {
+            0 => Some(None),
+            1 => Some(Some(self.u32()?)),
+            _ => None,
+        }
+    }
+
+    fn f32(&mut self) -> Option<f32> {
+        self.take().map(f32::from_le_bytes)
+    }
+
+    fn vec3(&mut self) -> Option<Vec3> {
+        Some(Vec3::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    fn quat(&mut self) -> Option<Quat> {
+        Some(Quat::from_xyzw(
+            self.f32()?,
+            self.f32()?,
+            self.f32()?,
+            self.f32()?,
+        ))
+    }
+
+    fn projectile_kind(&mut self) -> Option<ProjectileKind> {
+        match self.u8()? {
+            0 => Some(ProjectileKind::Push),
+            1 => Some(ProjectileKind::Pull),
+            2 => Some(ProjectileKind::Mass),
+            3 => Some(ProjectileKind::Spread),
+            _ => None,
+        }
+    }
+
+    fn appearance(&mut self) -> Option<Appearance> {
+        Some(Appearance {
+            seed: self.u64()?,
+            composition: Composition {
+                rock: self.f32()?,
+                water: self.f32()?,
+                gas: self.f32()?,
+            },
+            tint: Color::rgb(self.f32()?, self.f32()?, self.f32()?),
+        })
+    }
+
+    // Trailing garbage means we misread the packet.
+    fn finish<T>(self, message: T) -> Option<T> {
+        self.bytes.is_empty().then_some(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client_messages() -> Vec<ClientMessage> {
+        vec![
+            ClientMessage::Hello,
+            ClientMessage::Goodbye,
+            ClientMessage::Craft {
+                translation: Vec3::new(1.0, -2.5, 300.0),
+                rotation: Quat::from_rotation_y(0.7),
+            },
+            ClientMessage::Impact {
+                planet: 42,
+                local_direction: Vec3::new(0.0, 0.6, -0.8),
+                kind: ProjectileKind::Spread,
+            },
+        ]
+    }
+
+    fn server_messages() -> Vec<ServerMessage> {
+        vec![
+            ServerMessage::Welcome { client_id: 7 },
+            ServerMessage::Snapshot(Snapshot {
+                tick: u64::MAX - 1,
+                planets: vec![
+                    PlanetState {
+                        id: 0,
+                        position: Vec3::new(10.0, 20.0, 30.0),
+                        velocity: Vec3::new(-1.0, 0.0, 0.5),
+                        mass: 12.5,
+                        appearance: Appearance {
+                            seed: 0xdead_beef_cafe,
+                            composition: Composition {
+                                rock: 0.5,
+                                water: 0.25,
+                                gas: 0.25,
+                            },
+                            tint: Color::rgb(0.2, 0.4, 0.6),
+                        },
+                        owner: Some(3),
+                    },
+                    PlanetState {
+                        id: 1,
+                        position: Vec3::ZERO,
+                        velocity: Vec3::ZERO,
+                        mass: 1.0,
+                        appearance: Appearance {
+                            tint: Color::rgb(1.0, 1.0, 1.0),
+                            ..default()
+                        },
+                        owner: None,
+                    },
+                ],
+                crafts: vec![CraftState {
+                    client_id: 3,
+                    translation: Vec3::new(4.0, 5.0, 6.0),
+                    rotation: Quat::from_rotation_x(-1.2),
+                }],
+                scores: vec![(3, 10), (4, 0)],
+            }),
+            ServerMessage::Snapshot(Snapshot {
+                tick: 0,
+                planets: vec![],
+                crafts: vec![],
+                scores: vec![],
+            }),
+        ]
+    }
+
+    #[test]
+    fn client_messages_round_trip() {
+        for message in client_messages() {
+            assert_eq!(ClientMessage::decode(&message.encode()), Some(message));
+        }
+    }
+
+    #[test]
+    fn server_messages_round_trip() {
+        for message in server_messages() {
+            assert_eq!(ServerMessage::decode(&message.encode()), Some(message));
+        }
+    }
+
+    #[test]
+    fn truncated_packets_are_rejected() {
+        for message in client_messages() {
+            let bytes = message.encode();
+            for length in 0..bytes.len() {
+                assert_eq!(ClientMessage::decode(&bytes[..length]), None, "{message:?}");
+            }
+        }
+        for message in server_messages() {
+            let bytes = message.encode();
+            for length in 0..bytes.len() {
+                assert_eq!(ServerMessage::decode(&bytes[..length]), None, "{message:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_packets_are_rejected() {
+        // Unknown message types.
+        assert_eq!(ClientMessage::decode(&[4]), None);
+        assert_eq!(ServerMessage::decode(&[2, 0, 0, 0, 0]), None);
+        // Trailing garbage.
+        assert_eq!(ClientMessage::decode(&[0, 0]), None);
+        let mut bytes = ServerMessage::Welcome { client_id: 1 }.encode();
+        bytes.push(0);
+        assert_eq!(ServerMessage::decode(&bytes), None);
+        // An unknown projectile kind.
+        let mut bytes = client_messages()[3].encode();
+        *bytes.last_mut().unwrap() = 9;
+        assert_eq!(ClientMessage::decode(&bytes), None);
+        // An owner that is neither there nor not there.
+        let mut bytes = ServerMessage::Snapshot(Snapshot {
+            tick: 0,
+            planets: vec![PlanetState {
+                id: 0,
+                position: Vec3::ZERO,
+                velocity: Vec3::ZERO,
+                mass: 1.0,
+                appearance: Appearance::default(),
+                owner: None,
+            }],
+            crafts: vec![],
+            scores: vec![],
+        })
+        .encode();
+        // The owner comes just before the (empty) craft and score counts.
+        let owner = bytes.len() - 9;
+        bytes[owner] = 2;
+        assert_eq!(ServerMessage::decode(&bytes), None);
+        // More planets than the packet holds.
+        let mut bytes = server_messages()[2].encode();
+        bytes[9] = 1;
+        assert_eq!(ServerMessage::decode(&bytes), None);
+    }
+
+    #[test]
+    fn implausible_numbers_are_caught() {
+        for message in client_messages() {
+            assert!(message.is_plausible(), "{message:?}");
+        }
+        for local_direction in [Vec3::ZERO, Vec3::NAN, Vec3::new(f32::INFINITY, 0.0, 0.0)] {
+            let message = ClientMessage::Impact {
+                planet: 0,
+                local_direction,
+                kind: ProjectileKind::Push,
+            };
+            // They still cross the wire intact. It is the server that has to refuse them.
+            let decoded = ClientMessage::decode(&message.encode()).unwrap();
+            assert!(!decoded.is_plausible(), "{local_direction:?}");
+        }
+        for (translation, rotation) in [
+            (Vec3::NAN, Quat::IDENTITY),
+            (Vec3::ZERO, Quat::from_xyzw(0.0, 0.0, 0.0, 0.0)),
+            (Vec3::ZERO, Quat::from_xyzw(f32::NAN, 0.0, 0.0, 1.0)),
+        ] {
+            let message = ClientMessage::Craft {
+                translation,
+                rotation,
+            };
+            assert!(!message.is_plausible(), "{message:?}");
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Duration;
+
+use super::{
+    ClientMessage, CraftState, NetId, PlanetState, ServerMessage, Snapshot, MAX_PACKET_SIZE,
+};
+use crate::appearance::Appearance;
+use crate::craft::{
+    projectile_delta, ProjectileCollisionEvent, ProjectileKind, SpacecraftConfig, Weapon,
+};
+use crate::physics::{DeltaEvent, Momentum};
+use crate::scoring::{Owner, PlanetHitEvent, Player, Score};
+use crate::AppState;
+
+// A client that has been silent for this long is dropped.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+// Hits arrive in bursts after their projectiles' flight time and the network's jitter, so a peer's
+// gun may run this much hotter here than it can on the client before we stop believing it.
+const HEAT_TOLERANCE: f32 = 0.5;
+
+pub struct ServerPlugin {
+    pub bind: SocketAddr,
+}
+
+impl Plugin for ServerPlugin {
+    fn build(&self, app: &mut App) {
+        let socket = UdpSocket::bind(self.bind).expect("Could not bind server socket");
+        socket
+            .set_nonblocking(true)
+            .expect("Could not make server socket non-blocking");
+        info!("Serving on {}", self.bind);
+        // Projectiles only exist on clients, but `handle_planet_collisions` still expects this.
+        app.add_event::<ProjectileCollisionEvent>()
+            // Hits reported by clients are applied with the server's settings, not theirs.
+            .init_resource::<SpacecraftConfig>()
+            // The shared planet system runs forever. There are no rounds.
+            .insert_resource(NextState(Some(AppState::Playing)))
+            .insert_resource(ServerSocket {
+                socket,
+                clients: HashMap::new(),
+                next_client_id: 0,
+                tick: 0,
+            })
+            .add_systems(
+                Update,
+                (
+                    assign_net_ids,
+                    server_receive.after(assign_net_ids),
+                    server_broadcast.after(server_receive),
+                ),
+            );
+    }
+}
+
+pub struct Peer {
+    pub client_id: u32,
+    pub last_heard: Duration,
+    pub craft: Option<(Vec3, Quat)>,
+    // How hot this peer's gun must be, by the hits it has reported, and when that was worked out.
+    heat: f32,
+    cooled_at: Duration,
+}
+
+impl Peer {
+    fn new(client_id: u32, now: Duration) -> Self {
+        Self {
+            client_id,
+            last_heard: now,
+            craft: None,
+            heat: 0.0,
+            cooled_at: now,
+        }
+    }
+
+    /// Whether a hit by `kind` is one this peer's gun could have fired. Clients heat and jam their
+    /// own guns, but nothing stops a client from lying, so we keep track too.
+    fn heat_up(&mut self, kind: ProjectileKind, now: Duration) -> bool {
+        let weapon = Weapon::new(kind);
+        let cooled = weapon.cooling * (now - self.cooled_at).as_secs_f32();
+        self.heat = (self.heat - cooled).max(0.0);
+        self.cooled_at = now;
+        // Every pellet of a shot hits separately.
+        let heat = self.heat + weapon.heat_per_shot / weapon.pellets as f32;
+        if heat > 1.0 + HEAT_TOLERANCE {
+            return false;
+        }
+        self.heat = heat;
+        true
+    }
+}
+
+#[derive(Resource)]
+pub struct ServerSocket {
+    socket: UdpSocket,
+    pub clients: HashMap<SocketAddr, Peer>,
+    next_client_id: u32,
+    tick: u64,
+}
+
+pub fn assign_net_ids(
+    mut commands: Commands,
+    planet_query: Query<Entity, (With<Momentum>, Without<NetId>)>,
+    mut next_id: Local<u32>,
+) {
+    for planet in planet_query.iter() {
+        commands.entity(planet).insert(NetId(*next_id));
+        debug!("Planet {planet:?} is known to clients as {:?}", *next_id);
+        *next_id += 1;
+    }
+}
+
+pub fn server_receive(
+    mut server: ResMut<ServerSocket>,
+    mut planet_query: Query<(Entity, &NetId, &mut Momentum)>,
+    mut delta_events: EventWriter<DeltaEvent>,
+    mut hit_events: EventWriter<PlanetHitEvent>,
+    config: Res<SpacecraftConfig>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed();
+    let mut buffer = [0; MAX_PACKET_SIZE];
+    loop {
+        let (length, address) = match server.socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+            Err(error) => {
+                warn!("While receiving from clients: {error}");
+                break;
+            }
+        };
+        let Some(message) = ClientMessage::decode(&buffer[..length]) else {
+            warn!("Discarding malformed packet from {address}");
+            continue;
+        };
+        if !message.is_plausible() {
+            warn!("Discarding {message:?} from {address}");
+            continue;
+        }
+        trace!("Received {message:?} from {address}");
+        match message {
+            ClientMessage::Hello => {
+                let client_id = match server.clients.get(&address) {
+                    Some(peer) => peer.client_id,
+                    None => {
+                        let client_id = server.next_client_id;
+                        server.next_client_id += 1;
+                        info!("Client {client_id} connected from {address}");
+                        server.clients.insert(address, Peer::new(client_id, now));
+                        client_id
+                    }
+                };
+                server.send(address, &ServerMessage::Welcome { client_id });
+            }
+            ClientMessage::Goodbye => {
+                if let Some(peer) = server.clients.remove(&address) {
+                    info!("Client {} disconnected", peer.client_id);
+                }
+            }
+            ClientMessage::Craft {
+                translation,
+                rotation,
+            } => {
+                if let Some(peer) = server.clients.get_mut(&address) {
+                    peer.craft = Some((translation, rotation.normalize()));
+                }
+            }
+            ClientMessage::Impact {
+                planet,
+                local_direction,
+                kind,
+            } => {
+                let Some(peer) = server.clients.get_mut(&address) else {
+                    continue;
+                };
+                let player = Player(peer.client_id);
+                if !peer.heat_up(kind, now) {
+                    debug!("Client at {address} is hitting faster than its gun can fire");
+                    continue;
+                }
+                if let Some((entity, _, mut momentum)) =
+                    planet_query.iter_mut().find(|(_, id, _)| id.0 == planet)
+                {
+                    // Clients only say what they fired. How hard it hits is up to us.
+                    let impulse = Weapon::new(kind).hit_impulse(&config);
+                    let delta =
+                        projectile_delta(entity, &mut momentum, kind, impulse, local_direction);
+                    debug!(
+                        "Client at {address} hit planet {entity:?} with {kind}, delta_v={:?}",
+                        delta.delta_v
+                    );
+                    delta_events.send(delta);
+                    hit_events.send(PlanetHitEvent {
+                        planet: entity,
+                        player,
+                        impulse,
+                    });
+                }
+            }
+        }
+        if let Some(peer) = server.clients.get_mut(&address) {
+            peer.last_heard = now;
+        }
+    }
+}
+
+pub fn server_broadcast(
+    mut server: ResMut<ServerSocket>,
+    planet_query: Query<(&NetId, &Transform, &Momentum, &Owner, &Appearance)>,
+    score: Res<Score>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed();
+    server.clients.retain(|address, peer| {
+        let alive = now - peer.last_heard < CLIENT_TIMEOUT;
+        if !alive {
+            info!("Client {} at {address} timed out", peer.client_id);
+        }
+        alive
+    });
+    server.tick += 1;
+
+    let planets = planet_query
+        .iter()
+        .map(|(id, transform, momentum, owner, appearance)| PlanetState {
+            id: id.0,
+            position: transform.translation,
+            velocity: momentum.velocity,
+            mass: momentum.mass,
+            appearance: *appearance,
+            owner: owner.player.map(|player| player.0),
+        })
+        .collect::<Vec<_>>();
+    let crafts = server
+        .clients
+        .values()
+        .filter_map(|peer| {
+            peer.craft.map(|(translation, rotation)| CraftState {
+                client_id: peer.client_id,
+                translation,
+                rotation,
+            })
+        })
+        .collect::<Vec<_>>();
+    let scores = score
+        .points
+        .iter()
+        .map(|(player, points)| (player.0, *points))
+        .collect::<Vec<_>>();
+
+    for (address, peer) in server.clients.iter() {
+        // Nobody needs to be told where they are.
+        let message = ServerMessage::Snapshot(Snapshot {
+            tick: server.tick,
+            planets: planets.clone(),
+            crafts: crafts
+                .iter()
+                .filter(|craft| craft.client_id != peer.client_id)
+                .cloned()
+                .collect(),
+            scores: scores.clone(),
+        });
+        server.send(*address, &message);
+    }
+}
+
+impl ServerSocket {
+    fn send(&self, address: SocketAddr, message: &ServerMessage) {
+        if let Err(error) = self.socket.send_to(&message.encode(), address) {
+            warn!("While sending to {address}: {error}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peers_cannot_hit_faster_than_they_fire() {
+        let mut peer = Peer::new(0, Duration::ZERO);
+        let weapon = Weapon::new(ProjectileKind::Mass);
+        let most = ((1.0 + HEAT_TOLERANCE) / weapon.heat_per_shot) as usize;
+        let accepted = (0..100)
+            .filter(|_| peer.heat_up(ProjectileKind::Mass, Duration::ZERO))
+            .count();
+        assert_eq!(accepted, most);
+
+        // Cooled right down, it can fire again.
+        let cold = Duration::from_secs_f32(2.0 / weapon.cooling);
+        assert!(peer.heat_up(ProjectileKind::Mass, cold));
+    }
+
+    #[test]
+    fn every_pellet_of_a_spread_shot_counts_as_part_of_one() {
+        let mut peer = Peer::new(0, Duration::ZERO);
+        let weapon = Weapon::new(ProjectileKind::Spread);
+        let shots = (1.0 / weapon.heat_per_shot) as u32;
+        for _ in 0..shots * weapon.pellets {
+            assert!(peer.heat_up(ProjectileKind::Spread, Duration::ZERO));
+        }
+    }
+}
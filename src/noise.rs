@@ -0,0 +1,55 @@
+use bevy::prelude::*;
+
+pub(crate) fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Octaves of value noise, each twice the frequency and half the amplitude of the last.
+/// Roughly 0.0 to 1.0.
+pub(crate) fn fbm(seed: u64, point: Vec3, octaves: u32) -> f32 {
+    let (mut sum, mut amplitude, mut total) = (0.0, 1.0, 0.0);
+    for octave in 0..octaves {
+        sum += value_noise(
+            seed.wrapping_add(octave as u64),
+            point * (1 << octave) as f32,
+        ) * amplitude;
+        total += amplitude;
+        amplitude /= 2.0;
+    }
+    sum / total
+}
+
+fn value_noise(seed: u64, point: Vec3) -> f32 {
+    let cell = point.floor();
+    let t = point - cell;
+    let t = t * t * (Vec3::splat(3.0) - 2.0 * t);
+    let corner = |dx: i32, dy: i32, dz: i32| {
+        lattice(
+            seed,
+            cell.x as i32 + dx,
+            cell.y as i32 + dy,
+            cell.z as i32 + dz,
+        )
+    };
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let x00 = lerp(corner(0, 0, 0), corner(1, 0, 0), t.x);
+    let x10 = lerp(corner(0, 1, 0), corner(1, 1, 0), t.x);
+    let x01 = lerp(corner(0, 0, 1), corner(1, 0, 1), t.x);
+    let x11 = lerp(corner(0, 1, 1), corner(1, 1, 1), t.x);
+    lerp(lerp(x00, x10, t.y), lerp(x01, x11, t.y), t.z)
+}
+
+// A random value in 0.0..1.0 for each point of the integer lattice.
+fn lattice(seed: u64, x: i32, y: i32, z: i32) -> f32 {
+    let mut hash = seed
+        ^ (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ (z as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xC4CE_B9FE_1A85_EC53);
+    hash ^= hash >> 33;
+    (hash >> 40) as f32 / (1u64 << 24) as f32
+}
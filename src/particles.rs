@@ -0,0 +1,588 @@
+use bevy::prelude::*;
+use rand::Rng;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use crate::appearance::Appearance;
+use crate::clock::SimulationClock;
+use crate::craft::{ProjectileCollisionEvent, Spacecraft};
+use crate::mass_to_radius;
+use crate::physics::{Momentum, PhysicsConfig, PlanetMergeEvent};
+
+const SHOCKWAVE_SECONDS: f32 = 1.2;
+// How far a shockwave spreads, as a multiple of the merged planet's radius.
+const SHOCKWAVE_REACH: f32 = 4.0;
+const SHOCKWAVE_COLOR: Color = Color::rgb(1.0, 0.8, 0.5);
+// Steps a shockwave fades out in. Each is one material, shared by every shockwave.
+const SHOCKWAVE_FADES: usize = 16;
+// Trails are drawn through a point this often, in simulated time.
+const TRAIL_SPACING: Duration = Duration::from_millis(50);
+
+#[derive(Resource)]
+pub struct ParticleConfig {
+    /// The most particles alive at once. Past this, the oldest are recycled first.
+    pub pool_size: usize,
+    pub impacts: bool,
+    pub merges: bool,
+    /// A ring spreading out from where two planets merged.
+    pub shockwaves: bool,
+    /// Chunks knocked off planets by impacts and merges.
+    pub debris: bool,
+    pub exhaust: bool,
+    /// Exhaust particles per second from one thruster at full burn.
+    pub exhaust_rate: f32,
+}
+
+impl Default for ParticleConfig {
+    fn default() -> Self {
+        Self {
+            pool_size: 2000,
+            impacts: true,
+            merges: true,
+            shockwaves: true,
+            debris: true,
+            exhaust: true,
+            exhaust_rate: 60.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    /// A quick spray off the surface where a projectile hit.
+    Impact,
+    /// A big, slow flash where two planets met.
+    Merge,
+    /// Tumbling chunks of planet. These are lit, where the others glow.
+    Debris,
+    Exhaust,
+}
+
+struct Emitter {
+    speed: (f32, f32),
+    /// How far particles stray from the emitter's direction. 0.0 is not at all.
+    spread: f32,
+    lifetime: (f32, f32),
+    size: (f32, f32),
+    /// Fraction of velocity lost per second.
+    drag: f32,
+    /// Radians per second, at most.
+    spin: f32,
+}
+
+impl Effect {
+    fn emitter(&self) -> Emitter {
+        match self {
+            Effect::Impact => Emitter {
+                speed: (2.0, 6.0),
+                spread: 0.6,
+                lifetime: (0.3, 0.8),
+                size: (0.08, 0.15),
+                drag: 2.0,
+                spin: 0.0,
+            },
+            Effect::Merge => Emitter {
+                speed: (3.0, 10.0),
+                spread: 1.5,
+                lifetime: (0.6, 1.5),
+                size: (0.15, 0.3),
+                drag: 1.0,
+                spin: 0.0,
+            },
+            Effect::Debris => Emitter {
+                speed: (1.0, 4.0),
+                spread: 0.8,
+                lifetime: (1.5, 3.0),
+                size: (0.05, 0.15),
+                drag: 0.2,
+                spin: 4.0,
+            },
+            Effect::Exhaust => Emitter {
+                speed: (4.0, 6.0),
+                spread: 0.15,
+                lifetime: (0.2, 0.4),
+                size: (0.04, 0.08),
+                drag: 0.5,
+                spin: 0.0,
+            },
+        }
+    }
+}
+
+#[derive(Event, Debug, Clone)]
+pub struct EmitParticles {
+    pub effect: Effect,
+    pub position: Vec3,
+    /// The emitter's own velocity, which every particle starts out with.
+    pub velocity: Vec3,
+    /// Which way the particles mostly go. Zero for every which way.
+    pub direction: Vec3,
+    pub count: usize,
+    pub color: Color,
+}
+
+#[derive(Component, Default)]
+pub struct Particle {
+    velocity: Vec3,
+    age: f32,
+    lifetime: f32,
+    size: f32,
+    drag: f32,
+    spin: Vec3,
+}
+
+impl Particle {
+    pub fn is_live(&self) -> bool {
+        self.age < self.lifetime
+    }
+
+    /// Burnt out early. It stays wherever it was until it is hidden as well.
+    pub fn retire(&mut self) {
+        self.age = self.lifetime;
+    }
+}
+
+/// Every particle there will ever be, spawned up front and handed out round-robin.
+#[derive(Resource, Default)]
+pub struct ParticlePool {
+    particles: Vec<Entity>,
+    next: usize,
+    sphere: Handle<Mesh>,
+    chunk: Handle<Mesh>,
+    // Shared by every particle of the same color (and lighting).
+    materials: HashMap<([u8; 4], bool), Handle<StandardMaterial>>,
+}
+
+pub fn particle_pool_setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    config: Res<ParticleConfig>,
+) {
+    let sphere = meshes.add(
+        Mesh::try_from(shape::Icosphere {
+            radius: 1.0,
+            subdivisions: 1,
+        })
+        .unwrap(),
+    );
+    let chunk = meshes.add(Mesh::from(shape::Cube { size: 1.5 }));
+    let particles = (0..config.pool_size)
+        .map(|_| {
+            commands
+                .spawn(PbrBundle {
+                    mesh: sphere.clone(),
+                    visibility: Visibility::Hidden,
+                    ..default()
+                })
+                .insert(Particle::default())
+                .id()
+        })
+        .collect();
+    commands.insert_resource(ParticlePool {
+        particles,
+        sphere,
+        chunk,
+        ..default()
+    });
+}
+
+type PooledParticles<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Particle,
+        &'static mut Transform,
+        &'static mut Visibility,
+        &'static mut Handle<Mesh>,
+        &'static mut Handle<StandardMaterial>,
+    ),
+>;
+
+pub fn emit_particles(
+    mut emit_events: EventReader<EmitParticles>,
+    mut pool: ResMut<ParticlePool>,
+    mut particle_query: PooledParticles,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if pool.particles.is_empty() {
+        return;
+    }
+    let mut rng = rand::thread_rng();
+    for event in emit_events.read() {
+        let emitter = event.effect.emitter();
+        let lit = event.effect == Effect::Debris;
+        let material = pool
+            .materials
+            .entry((event.color.as_rgba_u8(), lit))
+            .or_insert_with(|| {
+                materials.add(StandardMaterial {
+                    base_color: event.color,
+                    perceptual_roughness: 1.0,
+                    unlit: !lit,
+                    ..default()
+                })
+            })
+            .clone();
+        let mesh = if lit {
+            pool.chunk.clone()
+        } else {
+            pool.sphere.clone()
+        };
+        let direction = event.direction.normalize_or_zero();
+        for _ in 0..event.count {
+            let slot = pool.particles[pool.next];
+            pool.next = (pool.next + 1) % pool.particles.len();
+            let Ok((
+                mut particle,
+                mut transform,
+                mut visibility,
+                mut particle_mesh,
+                mut particle_material,
+            )) = particle_query.get_mut(slot)
+            else {
+                continue;
+            };
+            let heading = (direction + random_direction(&mut rng) * emitter.spread)
+                .try_normalize()
+                .unwrap_or_else(|| random_direction(&mut rng));
+            let speed = rng.gen_range(emitter.speed.0..emitter.speed.1);
+            *particle = Particle {
+                velocity: event.velocity + heading * speed,
+                age: 0.0,
+                lifetime: rng.gen_range(emitter.lifetime.0..emitter.lifetime.1),
+                size: rng.gen_range(emitter.size.0..emitter.size.1),
+                drag: emitter.drag,
+                spin: random_direction(&mut rng) * rng.gen_range(0.0..=emitter.spin),
+            };
+            *transform =
+                Transform::from_translation(event.position).with_scale(Vec3::splat(particle.size));
+            *visibility = Visibility::Visible;
+            *particle_mesh = mesh.clone();
+            *particle_material = material.clone();
+        }
+    }
+}
+
+fn random_direction(rng: &mut impl Rng) -> Vec3 {
+    loop {
+        let candidate = Vec3::new(
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+        );
+        let length = candidate.length();
+        if length > f32::EPSILON && length <= 1.0 {
+            return candidate / length;
+        }
+    }
+}
+
+/// Particles coast, slow down and shrink away to nothing, and then go back in the pool.
+pub fn update_particles(
+    mut particle_query: Query<(&mut Particle, &mut Transform, &mut Visibility)>,
+    clock: Res<SimulationClock>,
+) {
+    let dt = clock.delta_seconds();
+    for (mut particle, mut transform, mut visibility) in particle_query.iter_mut() {
+        if !particle.is_live() {
+            continue;
+        }
+        particle.age += dt;
+        if !particle.is_live() {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+        let Particle {
+            velocity,
+            drag,
+            spin,
+            ..
+        } = *particle;
+        transform.translation += velocity * dt;
+        transform.rotate_local(Quat::from_scaled_axis(spin * dt));
+        particle.velocity = velocity * (-drag * dt).exp();
+        let burnt = particle.age / particle.lifetime;
+        transform.scale = Vec3::splat(particle.size * (1.0 - burnt * burnt));
+    }
+}
+
+pub fn impact_particles(
+    mut projectile_events: EventReader<ProjectileCollisionEvent>,
+    planet_query: Query<(&Transform, &Momentum, &Appearance)>,
+    mut emit_events: EventWriter<EmitParticles>,
+    config: Res<ParticleConfig>,
+) {
+    for event in projectile_events.read() {
+        let Ok((transform, momentum, appearance)) = planet_query.get(event.planet) else {
+            continue;
+        };
+        let burst = EmitParticles {
+            effect: Effect::Impact,
+            position: transform.translation + event.local_impact_site,
+            velocity: momentum.velocity,
+            direction: event.local_impact_site,
+            count: 12,
+            color: event.kind.color(),
+        };
+        if config.debris {
+            emit_events.send(EmitParticles {
+                effect: Effect::Debris,
+                count: 4,
+                color: appearance.tint,
+                ..burst.clone()
+            });
+        }
+        if config.impacts {
+            emit_events.send(burst);
+        }
+    }
+}
+
+pub fn merge_particles(
+    mut merge_events: EventReader<PlanetMergeEvent>,
+    planet_query: Query<(&Momentum, &Appearance)>,
+    mut emit_events: EventWriter<EmitParticles>,
+    config: Res<ParticleConfig>,
+) {
+    for event in merge_events.read() {
+        let Ok((momentum, appearance)) = planet_query.get(event.major) else {
+            continue;
+        };
+        let flash = EmitParticles {
+            effect: Effect::Merge,
+            position: event.position,
+            velocity: momentum.velocity,
+            direction: Vec3::ZERO,
+            count: 40,
+            color: Color::rgb(1.0, 0.8, 0.5),
+        };
+        if config.debris {
+            emit_events.send(EmitParticles {
+                effect: Effect::Debris,
+                count: 20,
+                color: appearance.tint,
+                ..flash.clone()
+            });
+        }
+        if config.merges {
+            emit_events.send(flash);
+        }
+    }
+}
+
+/// Exhaust out of whichever thrusters are firing.
+pub fn exhaust_particles(
+    spacecraft_query: Query<(&Transform, &Spacecraft)>,
+    mut emit_events: EventWriter<EmitParticles>,
+    config: Res<ParticleConfig>,
+    clock: Res<SimulationClock>,
+    mut owed: Local<f32>,
+) {
+    if !config.exhaust {
+        return;
+    }
+    for (transform, spacecraft) in spacecraft_query.iter() {
+        if spacecraft.fuel <= 0.0 {
+            continue;
+        }
+        let thrust = spacecraft.thrust.clamp(Vec3::splat(-1.0), Vec3::splat(1.0));
+        *owed += thrust.abs().dot(Vec3::ONE) * config.exhaust_rate * clock.delta_seconds();
+        let count = owed.floor();
+        *owed -= count;
+        let direction = -(transform.rotation * thrust).normalize_or_zero();
+        if count < 1.0 || direction == Vec3::ZERO {
+            continue;
+        }
+        emit_events.send(EmitParticles {
+            effect: Effect::Exhaust,
+            position: transform.translation + direction * 0.7,
+            velocity: spacecraft.velocity,
+            direction,
+            count: count as usize,
+            color: Color::rgb(0.4, 0.7, 1.0),
+        });
+    }
+}
+
+#[derive(Component)]
+pub struct Shockwave {
+    velocity: Vec3,
+    age: f32,
+    /// Radius of the ring as it starts out, and as it ends.
+    radii: (f32, f32),
+}
+
+/// One ring for every shockwave, and one material for each step of its fade.
+#[derive(Resource, Default)]
+pub struct ShockwaveAssets {
+    ring: Handle<Mesh>,
+    // From opaque to all but gone.
+    fades: Vec<Handle<StandardMaterial>>,
+}
+
+impl ShockwaveAssets {
+    fn fade(&self, t: f32) -> Handle<StandardMaterial> {
+        let step = (t * self.fades.len() as f32) as usize;
+        self.fades[step.min(self.fades.len() - 1)].clone()
+    }
+}
+
+pub fn shockwave_setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let ring = meshes.add(Mesh::from(shape::Torus {
+        radius: 1.0,
+        ring_radius: 0.03,
+        ..default()
+    }));
+    let fades = (0..SHOCKWAVE_FADES)
+        .map(|step| {
+            materials.add(StandardMaterial {
+                base_color: SHOCKWAVE_COLOR.with_a(1.0 - step as f32 / SHOCKWAVE_FADES as f32),
+                alpha_mode: AlphaMode::Add,
+                unlit: true,
+                ..default()
+            })
+        })
+        .collect();
+    commands.insert_resource(ShockwaveAssets { ring, fades });
+}
+
+pub fn spawn_shockwaves(
+    mut commands: Commands,
+    mut merge_events: EventReader<PlanetMergeEvent>,
+    planet_query: Query<(&Transform, &Momentum)>,
+    assets: Res<ShockwaveAssets>,
+    config: Res<ParticleConfig>,
+) {
+    if !config.shockwaves {
+        return;
+    }
+    for event in merge_events.read() {
+        let Ok((transform, momentum)) = planet_query.get(event.major) else {
+            continue;
+        };
+        // Around the line between the two planets' centers.
+        let axis = (event.position - transform.translation).normalize_or_zero();
+        let rotation = if axis == Vec3::ZERO {
+            Quat::IDENTITY
+        } else {
+            Quat::from_rotation_arc(Vec3::Y, axis)
+        };
+        let radii = (
+            mass_to_radius(event.absorbed_mass),
+            mass_to_radius(momentum.mass) * SHOCKWAVE_REACH,
+        );
+        commands
+            .spawn(PbrBundle {
+                mesh: assets.ring.clone(),
+                material: assets.fade(0.0),
+                transform: Transform::from_translation(event.position)
+                    .with_rotation(rotation)
+                    .with_scale(Vec3::splat(radii.0)),
+                ..default()
+            })
+            .insert(Shockwave {
+                velocity: momentum.velocity,
+                age: 0.0,
+                radii,
+            });
+    }
+}
+
+pub fn expand_shockwaves(
+    mut commands: Commands,
+    mut shockwave_query: Query<(
+        Entity,
+        &mut Shockwave,
+        &mut Transform,
+        &mut Handle<StandardMaterial>,
+    )>,
+    assets: Res<ShockwaveAssets>,
+    clock: Res<SimulationClock>,
+) {
+    let dt = clock.delta_seconds();
+    for (entity, mut shockwave, mut transform, mut material) in shockwave_query.iter_mut() {
+        shockwave.age += dt;
+        let t = shockwave.age / SHOCKWAVE_SECONDS;
+        if t >= 1.0 {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let (from, to) = shockwave.radii;
+        let spread = 1.0 - (1.0 - t) * (1.0 - t);
+        transform.translation += shockwave.velocity * dt;
+        transform.scale = Vec3::splat(from + (to - from) * spread);
+        material.set_if_neq(assets.fade(t));
+    }
+}
+
+/// Where a planet has been, oldest first, by the `SimulationClock`.
+#[derive(Component, Default)]
+pub struct Trail(VecDeque<(Duration, Vec3)>);
+
+pub fn record_trails(
+    mut commands: Commands,
+    mut planet_query: Query<(Entity, &Transform, Option<&mut Trail>), With<Momentum>>,
+    config: Res<PhysicsConfig>,
+    clock: Res<SimulationClock>,
+) {
+    if !config.trails {
+        return;
+    }
+    let now = clock.elapsed();
+    let ttl = Duration::from_millis(config.trail_ttl);
+    for (planet, transform, trail) in planet_query.iter_mut() {
+        let Some(mut trail) = trail else {
+            commands
+                .entity(planet)
+                .insert(Trail(VecDeque::from([(now, transform.translation)])));
+            continue;
+        };
+        let points = &mut trail.0;
+        // A rewind takes the planet back along its trail.
+        while points.back().is_some_and(|&(time, _)| time > now) {
+            points.pop_back();
+        }
+        while points.front().is_some_and(|&(time, _)| time + ttl < now) {
+            points.pop_front();
+        }
+        let due = match points.back() {
+            Some(&(time, _)) => now >= time + TRAIL_SPACING,
+            None => true,
+        };
+        if due {
+            points.push_back((now, transform.translation));
+        }
+    }
+}
+
+/// From where each planet is now, back along its trail, fading as it goes.
+pub fn draw_trails(
+    trail_query: Query<(&Transform, &Trail, &Appearance)>,
+    config: Res<PhysicsConfig>,
+    clock: Res<SimulationClock>,
+    mut gizmos: Gizmos,
+) {
+    if !config.trails {
+        return;
+    }
+    let now = clock.elapsed().as_secs_f32();
+    let ttl = config.trail_ttl as f32 / 1000.0;
+    for (transform, trail, appearance) in trail_query.iter() {
+        let faded = |time: Duration| {
+            let age = (now - time.as_secs_f32()).max(0.0);
+            appearance
+                .tint
+                .with_a((1.0 - age / ttl.max(f32::EPSILON)).clamp(0.0, 1.0) * 0.6)
+        };
+        let points = trail
+            .0
+            .iter()
+            .map(|&(time, position)| (position, faded(time)))
+            .chain([(transform.translation, faded(clock.elapsed()))]);
+        gizmos.linestrip_gradient(points);
+    }
+}
@@ -1,13 +1,25 @@
-use crate::craft::{ProjectileCollisionEvent, ProjectileTarget};
+use crate::appearance::Appearance;
+use crate::clock::{SimulationClock, STEP};
+use crate::craft::{LockedTarget, Projectile, ProjectileCollisionEvent, ProjectileTarget};
+use crate::scoring::{Owner, Player, ScoringConfig};
 use crate::{mass_to_radius, radius_to_mass};
 use bevy::prelude::*;
-use bevy_rapier3d::prelude::{ActiveEvents, Collider, CollisionEvent, RigidBody, Sensor};
+use bevy_rapier3d::prelude::{
+    ActiveEvents, Collider, ColliderScale, CollisionEvent, RigidBody, Sensor,
+};
+
+/// Scales the pull of every planet on every other.
+pub const GRAVITATIONAL_CONSTANT: f32 = 0.001;
 
 #[derive(Resource)]
 pub struct PhysicsConfig {
+    /// Planets leave a fading line behind them.
     pub trails: bool,
     pub sims_per_frame: u8,
+    /// How long a trail lasts, in milliseconds of simulated time.
     pub trail_ttl: u64,
+    /// How long a merged planet takes to grow into its new size. Zero for no time at all.
+    pub merge_seconds: f32,
 }
 
 impl Default for PhysicsConfig {
@@ -16,6 +28,7 @@ impl Default for PhysicsConfig {
             trails: false,
             sims_per_frame: 10,
             trail_ttl: 2500,
+            merge_seconds: 0.6,
         }
     }
 }
@@ -28,7 +41,7 @@ pub fn handle_planet_collisions(
     mut projectile_collision_events: EventWriter<ProjectileCollisionEvent>,
     mut planet_collision_events: EventWriter<PlanetCollisionEvent>,
     planet_query: Query<(&Transform, &Momentum)>,
-    projectile_query: Query<&Transform, With<ProjectileTarget>>,
+    projectile_query: Query<(&Transform, &Projectile)>,
 ) {
     for collision_event in events.read() {
         // FIXME: Filter events (for "Sensor")
@@ -39,7 +52,9 @@ pub fn handle_planet_collisions(
                 planet_collision_events.send(event);
             } else {
                 for (&projectile, &planet) in [(e0, e1), (e1, e0)] {
-                    if let Ok(projectile_transform) = projectile_query.get(projectile) {
+                    if let Ok((projectile_transform, projectile_state)) =
+                        projectile_query.get(projectile)
+                    {
                         if let Ok((planet_transform, planet_momentum)) = planet_query.get(planet) {
                             let radius = mass_to_radius(planet_momentum.mass);
                             // unit vector at planet center pointing at projectile
@@ -51,6 +66,8 @@ pub fn handle_planet_collisions(
                                 planet,
                                 projectile,
                                 local_impact_site,
+                                kind: projectile_state.kind,
+                                impulse: projectile_state.impulse,
                             };
                             debug!("Sending projectile impact event: {event:?}");
                             projectile_collision_events.send(event);
@@ -69,8 +86,16 @@ pub fn handle_despawn_planet(
     mut commands: Commands,
     mut despawn_planet_events: EventReader<DespawnPlanetEvent>,
     projectile_query: Query<(Entity, &ProjectileTarget)>,
+    // Absent on the dedicated server.
+    mut locked: Option<ResMut<LockedTarget>>,
 ) {
     for &DespawnPlanetEvent(entity) in despawn_planet_events.read() {
+        if let Some(locked) = locked.as_mut() {
+            if locked.planet == Some(entity) {
+                debug!("Target lock lost: planet {entity:?} is gone");
+                locked.planet = None;
+            }
+        }
         debug!("RECURSIVELY despawning planet {entity:?} and all of its in-flight projectiles");
         for (projectile, &ProjectileTarget { planet, .. }) in projectile_query.iter() {
             if entity == planet {
@@ -81,13 +106,39 @@ pub fn handle_despawn_planet(
     }
 }
 
-// FIXME: 1) this should be 'merge_planets' or something, 2) do we need to
-//        "transfer" children? (explosion animation...)
+/// Sent once two planets have become one.
+#[derive(Debug, Event)]
+pub struct PlanetMergeEvent {
+    pub major: Entity,
+    pub minor: Entity,
+    pub absorbed_mass: f32,
+    /// Where the two touched.
+    pub position: Vec3,
+    // Owner of the merged planet.
+    pub owner: Option<Player>,
+}
+
+type MergingPlanets<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Transform,
+        &'static mut Momentum,
+        Entity,
+        &'static mut Owner,
+        &'static mut Appearance,
+    ),
+>;
+
+// FIXME: this should be 'merge_planets' or something
 pub fn transfer_planet_momentum(
-    mut planet_query: Query<(&Transform, &mut Momentum, Entity)>,
+    mut commands: Commands,
+    mut planet_query: MergingPlanets,
     mut planet_events: EventReader<PlanetCollisionEvent>,
     mut delta_events: EventWriter<DeltaEvent>,
     mut despawn_planet_events: EventWriter<DespawnPlanetEvent>,
+    mut merge_events: EventWriter<PlanetMergeEvent>,
+    scoring_config: Res<ScoringConfig>,
 ) {
     for PlanetCollisionEvent(e0, e1) in planet_events.read() {
         // FIXME: We have write access to `Momentum` and yet we update
@@ -110,7 +161,11 @@ pub fn transfer_planet_momentum(
             debug!("  position: {:?}", minor.0.translation);
             debug!("  velocity: {:?}", minor.1.velocity);
             debug!("  mass: {:?}", minor.1.mass);
+            debug!("  owner: {:?}", minor.3.player);
 
+            let contact = major.0.translation
+                + (minor.0.translation - major.0.translation).normalize_or_zero()
+                    * mass_to_radius(major.1.mass);
             let combined_momentum =
                 (major.1.velocity * major.1.mass) + (minor.1.velocity * minor.1.mass);
             let combined_mass = major.1.mass + minor.1.mass;
@@ -133,18 +188,25 @@ pub fn transfer_planet_momentum(
                 major.2, minor.2
             );
             let delta_p = weighted_midpoint - major.0.translation;
-            // How much to scale in the linear (multiply major original
-            // radius by this much to achieve a proportionate mass (i.e. volume) increase.
-            let delta_s = major_factor.powf(-1.0 / 3.0);
+            // The size changes at once, but the mesh only catches up over a few frames.
+            animate_growth(&mut commands, entity, major.0);
             let event = DeltaEvent {
                 entity,
                 delta_p,
                 delta_v,
-                delta_s,
                 force_ro: Vec3::ZERO,
             };
             debug!("Sending event: {event:?}");
             delta_events.send(event);
+            major.3.absorb(&minor.3, scoring_config.ownership_rule);
+            major.4.absorb(&minor.4, minor_factor);
+            merge_events.send(PlanetMergeEvent {
+                major: entity,
+                minor: minor.2,
+                absorbed_mass: minor.1.mass,
+                position: contact,
+                owner: major.3.player,
+            });
             debug!("Signaling despawn request for minor planet {:?}", minor.2);
             despawn_planet_events.send(DespawnPlanetEvent(minor.2));
         }
@@ -157,8 +219,13 @@ pub struct PlanetBundle {
     momentum: Momentum,
     rigid_body: RigidBody,
     collider: Collider,
+    // `size_planets` sizes the collider itself, so the transform's scale (which is for the mesh)
+    // mustn't scale it again.
+    collider_scale: ColliderScale,
     active_events: ActiveEvents,
     sensor: Sensor,
+    owner: Owner,
+    appearance: Appearance,
 }
 
 impl Default for PlanetBundle {
@@ -168,8 +235,11 @@ impl Default for PlanetBundle {
             momentum: Default::default(),
             rigid_body: RigidBody::Dynamic,
             collider: Default::default(),
+            collider_scale: ColliderScale::Absolute(Vec3::ONE),
             active_events: ActiveEvents::COLLISION_EVENTS,
             sensor: Default::default(),
+            owner: Default::default(),
+            appearance: Default::default(),
         }
     }
 }
@@ -178,23 +248,25 @@ pub fn spawn_planet<'a>(
     radius: f32,
     position: Vec3,
     velocity: Vec3,
-    color: Color,
+    appearance: Appearance,
     commands: &'a mut Commands,
     meshes: &'a mut ResMut<Assets<Mesh>>,
     materials: &'a mut ResMut<Assets<StandardMaterial>>,
-) {
+) -> Entity {
     let mass = radius_to_mass(radius);
     let planet = PlanetBundle {
         pbr: PbrBundle {
             mesh: meshes.add(
-                Mesh::try_from(shape::Icosphere {
-                    radius,
-                    ..default()
-                })
-                .unwrap(),
+                // UV spheres, rather than icospheres, so the generated surfaces wrap cleanly.
+                Mesh::from(shape::UVSphere {
+                    radius: 1.0,
+                    sectors: 48,
+                    stacks: 24,
+                }),
             ),
-            material: materials.add(color.into()),
-            transform: Transform::from_translation(position),
+            // `paint_planets` covers this with a surface, where there is anyone to see it.
+            material: materials.add(appearance.tint.into()),
+            transform: Transform::from_translation(position).with_scale(Vec3::splat(radius)),
             ..default()
         },
         momentum: Momentum {
@@ -203,10 +275,12 @@ pub fn spawn_planet<'a>(
             ..Default::default()
         },
         collider: Collider::ball(radius),
+        appearance,
         ..Default::default()
     };
     let planet_id = commands.spawn(planet).id();
     debug!("Spawned planet={planet_id:?}");
+    planet_id
 }
 
 #[derive(Component, Debug, Default)]
@@ -221,22 +295,27 @@ pub struct DeltaEvent {
     pub entity: Entity,
     pub delta_p: Vec3,
     pub delta_v: Vec3,
-    pub delta_s: f32,
     pub force_ro: Vec3,
 }
 
 pub fn signal_freefall_delta(
     planet_query: Query<(Entity, &Transform, &Momentum)>,
-    time: Res<Time>,
+    clock: Res<SimulationClock>,
     physics_config: Res<PhysicsConfig>,
     mut delta_events: EventWriter<DeltaEvent>,
 ) {
-    let dt = time.delta_seconds();
+    // Stopped: nothing moves, so don't work out how it would.
+    if clock.delta().is_zero() {
+        return;
+    }
+    // Fast forward takes more, shorter passes rather than fewer, longer ones, so orbits hold.
+    let passes = (clock.delta_seconds() / STEP.as_secs_f32()).ceil().max(1.0) as u32;
+    let dt = clock.delta_seconds() / passes as f32;
     let mut masses = planet_query
         .iter()
         .map(|t| (t.0, t.1.translation, t.2.mass, t.2.velocity))
         .collect::<Vec<_>>();
-    for _ in 0..physics_config.sims_per_frame {
+    for _ in 0..physics_config.sims_per_frame as u32 * passes {
         let accelerations = masses.iter().map(|particle1| {
             masses.iter().fold(Vec3::ZERO, |acceleration, particle2| {
                 let dir = particle2.1 - particle1.1;
@@ -246,7 +325,7 @@ pub fn signal_freefall_delta(
                 } else {
                     dir
                 };
-                acceleration + grav_acc * 0.001
+                acceleration + grav_acc * GRAVITATIONAL_CONSTANT
             })
         });
         // What would happen if each pass re-re-randomized the order of masses? Good?
@@ -258,12 +337,10 @@ pub fn signal_freefall_delta(
                 let force = acceleration * dt;
                 let delta_p = *velocity * dt;
                 let delta_v = force / *mass;
-                let delta_s = 1.0;
                 delta_events.send(DeltaEvent {
                     entity: *entity,
                     delta_p,
                     delta_v,
-                    delta_s,
                     force_ro: force,
                 });
                 (*entity, *translation + delta_p, *mass, *velocity + delta_v)
@@ -281,7 +358,189 @@ pub fn handle_freefall(
             transform.translation += event.delta_p;
             momentum.velocity += event.delta_v;
             momentum.force_ro += event.force_ro;
-            transform.scale *= event.delta_s;
         }
     }
 }
+
+/// Eases a planet's mesh from the size it was to the size it has become in a merge. Only the
+/// mesh lags behind: the collider, like everything else, is the new size from the start.
+#[derive(Component, Debug)]
+pub struct MergeAnimation {
+    /// The radius the mesh was shown at when the merge happened.
+    pub from: f32,
+    elapsed: f32,
+}
+
+/// Start easing a planet's mesh from the size it is shown at now into the size its mass calls
+/// for. If it was already easing, it carries on from wherever it had got to.
+pub fn animate_growth(commands: &mut Commands, planet: Entity, transform: &Transform) {
+    commands.entity(planet).insert(MergeAnimation {
+        from: transform.scale.x,
+        elapsed: 0.0,
+    });
+}
+
+type SizedPlanets<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Momentum,
+        &'static mut Transform,
+        &'static mut Collider,
+        Option<&'static mut MergeAnimation>,
+    ),
+>;
+
+/// The one place a planet's size is set: both the mesh (a unit sphere, scaled) and the collider
+/// follow from its mass. The mesh may lag behind after a merge; the collider never does.
+pub fn size_planets(
+    mut commands: Commands,
+    mut planet_query: SizedPlanets,
+    physics_config: Res<PhysicsConfig>,
+    clock: Res<SimulationClock>,
+) {
+    for (planet, momentum, mut transform, mut collider, animation) in planet_query.iter_mut() {
+        let radius = mass_to_radius(momentum.mass);
+        if collider.as_ball().map(|ball| ball.radius()) != Some(radius) {
+            *collider = Collider::ball(radius);
+        }
+        let shown = match animation {
+            Some(mut animation) => {
+                animation.elapsed += clock.delta_seconds();
+                let t = if physics_config.merge_seconds > 0.0 {
+                    animation.elapsed / physics_config.merge_seconds
+                } else {
+                    1.0
+                };
+                if t >= 1.0 {
+                    commands.entity(planet).remove::<MergeAnimation>();
+                    radius
+                } else {
+                    // Quick to start with, settling gently into the new size.
+                    let eased = 1.0 - (1.0 - t).powi(3);
+                    animation.from + (radius - animation.from) * eased
+                }
+            }
+            None => radius,
+        };
+        let scale = Vec3::splat(shown);
+        if transform.scale != scale {
+            transform.scale = scale;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::tick_simulation_clock;
+
+    fn merging_app(merge_seconds: f32) -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(PhysicsConfig {
+                merge_seconds,
+                ..default()
+            })
+            .init_resource::<ScoringConfig>()
+            .init_resource::<SimulationClock>()
+            .add_event::<PlanetCollisionEvent>()
+            .add_event::<DeltaEvent>()
+            .add_event::<DespawnPlanetEvent>()
+            .add_event::<PlanetMergeEvent>()
+            .add_systems(PreUpdate, tick_simulation_clock)
+            .add_systems(
+                Update,
+                (
+                    transfer_planet_momentum,
+                    handle_freefall.after(transfer_planet_momentum),
+                    handle_despawn_planet.after(transfer_planet_momentum),
+                    size_planets.after(handle_freefall),
+                ),
+            );
+        app
+    }
+
+    fn spawn(app: &mut App, mass: f32, position: Vec3) -> Entity {
+        app.world
+            .spawn((
+                Transform::from_translation(position),
+                Momentum { mass, ..default() },
+                Collider::default(),
+                Owner::default(),
+                Appearance::default(),
+            ))
+            .id()
+    }
+
+    // The radius the mesh is shown at, and the radius of the collider.
+    fn radii(app: &App, planet: Entity) -> (f32, f32) {
+        let transform = app.world.get::<Transform>(planet).unwrap();
+        let collider = app.world.get::<Collider>(planet).unwrap();
+        (transform.scale.x, collider.as_ball().unwrap().radius())
+    }
+
+    fn merge(app: &mut App, e0: Entity, e1: Entity) -> Entity {
+        app.world.send_event(PlanetCollisionEvent(e0, e1));
+        app.update();
+        match (app.world.get_entity(e0), app.world.get_entity(e1)) {
+            (Some(_), None) => e0,
+            (None, Some(_)) => e1,
+            _ => panic!("Expected exactly one of {e0:?} and {e1:?} to survive the merge"),
+        }
+    }
+
+    #[test]
+    fn size_follows_mass_through_chains_of_merges() {
+        let mut app = merging_app(0.0);
+        let masses = [3.0, 1.0, 40.0, 0.5, 12.0, 12.0, 250.0];
+        let planets: Vec<_> = masses
+            .iter()
+            .enumerate()
+            .map(|(i, &mass)| spawn(&mut app, mass, Vec3::X * 20.0 * i as f32))
+            .collect();
+        app.update();
+
+        let mut survivor = planets[0];
+        let mut total = masses[0];
+        for (&planet, &mass) in planets.iter().zip(masses.iter()).skip(1) {
+            survivor = merge(&mut app, survivor, planet);
+            total += mass;
+            // Let the mesh settle.
+            app.update();
+            let momentum = app.world.get::<Momentum>(survivor).unwrap();
+            assert!((momentum.mass - total).abs() < 1e-3);
+            let radius = mass_to_radius(momentum.mass);
+            let (shown, collider) = radii(&app, survivor);
+            assert!(
+                (shown - radius).abs() < 1e-5,
+                "mesh {shown} for radius {radius}"
+            );
+            assert!(
+                (collider - radius).abs() < 1e-5,
+                "collider {collider} for radius {radius}"
+            );
+        }
+    }
+
+    #[test]
+    fn collider_does_not_wait_for_the_mesh() {
+        let mut app = merging_app(60.0);
+        let major = spawn(&mut app, 30.0, Vec3::ZERO);
+        let minor = spawn(&mut app, 20.0, Vec3::X * 4.0);
+        app.update();
+        let before = mass_to_radius(30.0);
+
+        let survivor = merge(&mut app, major, minor);
+        app.update();
+        let after = mass_to_radius(50.0);
+        let (shown, collider) = radii(&app, survivor);
+        assert!((collider - after).abs() < 1e-5);
+        assert!(
+            shown >= before && shown < after,
+            "mesh {shown} between {before} and {after}"
+        );
+        assert!(app.world.get::<MergeAnimation>(survivor).is_some());
+    }
+}
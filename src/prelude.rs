@@ -1,4 +1,18 @@
+pub use crate::appearance::*;
+pub use crate::bindings::*;
+pub use crate::clock::*;
 pub use crate::craft::*;
 pub use crate::helpscreen::*;
+pub use crate::hud::*;
+pub use crate::inspector::*;
+pub use crate::networking::*;
+pub use crate::particles::*;
 pub use crate::physics::*;
+pub use crate::radar::*;
+pub use crate::rewind::*;
+pub use crate::round::*;
+pub use crate::scoring::*;
+pub use crate::settings::*;
+pub use crate::sky::*;
+pub use crate::sound::*;
 pub use crate::*;
@@ -0,0 +1,227 @@
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{self, Align2, Color32, Frame, Id, LayerId, Order, Pos2, Sense, Shape, Stroke, Window},
+    EguiContexts,
+};
+
+use crate::craft::{LeftCamera, Spacecraft};
+use crate::mass_to_radius;
+use crate::physics::Momentum;
+use crate::scoring::{LocalPlayer, Owner, Player};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RadarColoring {
+    /// Ours are green, unowned are grey, everybody else gets a color of their own.
+    #[default]
+    Ownership,
+    /// Red for closing, blue for opening, grey for holding steady.
+    ApproachRate,
+}
+
+#[derive(Resource)]
+pub struct RadarConfig {
+    /// Planets further away than this sit on the rim of the scope.
+    pub range: f32,
+    pub coloring: RadarColoring,
+    pub show_radar: bool,
+    pub show_offscreen_indicators: bool,
+}
+
+impl Default for RadarConfig {
+    fn default() -> Self {
+        Self {
+            range: 60.0,
+            coloring: RadarColoring::Ownership,
+            show_radar: true,
+            show_offscreen_indicators: true,
+        }
+    }
+}
+
+type PlanetBlips<'w, 's> = Query<'w, 's, (&'static Transform, &'static Momentum, &'static Owner)>;
+
+/// An arrow on the edge of the screen for every planet we can't see.
+pub fn offscreen_indicators(
+    mut ctx: EguiContexts,
+    spacecraft_query: Query<(&Transform, &Spacecraft)>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<LeftCamera>>,
+    planet_query: PlanetBlips,
+    local_player: Res<LocalPlayer>,
+    config: Res<RadarConfig>,
+) {
+    if !config.show_offscreen_indicators {
+        return;
+    }
+    let (Ok((transform, spacecraft)), Some((camera, camera_transform))) =
+        (spacecraft_query.get_single(), camera_query.iter().next())
+    else {
+        return;
+    };
+    let ctx = ctx.ctx_mut();
+    let screen = ctx.screen_rect().shrink(20.0);
+    let painter = ctx.layer_painter(LayerId::new(Order::Background, Id::new("offscreen")));
+    for (planet_transform, momentum, owner) in planet_query.iter() {
+        let position = planet_transform.translation;
+        if let Some(on_screen) = camera.world_to_viewport(camera_transform, position) {
+            if screen.contains(Pos2::new(on_screen.x, on_screen.y)) {
+                continue;
+            }
+        }
+        // Which way to turn to see it, as seen from the cockpit. Screen y points down.
+        let local = transform.rotation.inverse() * (position - transform.translation);
+        let direction = egui::vec2(local.x, -local.y);
+        if direction.length() < f32::EPSILON {
+            continue;
+        }
+        let direction = direction.normalized();
+        // Push out from the center along `direction` until we reach the edge of the screen.
+        let center = screen.center();
+        let half = screen.size() / 2.0;
+        let reach = (half.x / direction.x.abs()).min(half.y / direction.y.abs());
+        let tip = center + direction * reach;
+        let size = 6.0 + mass_to_radius(momentum.mass) * 2.0;
+        let color = blip_color(
+            &config,
+            owner,
+            local_player.0,
+            position - transform.translation,
+            momentum.velocity - spacecraft.velocity,
+        );
+        let normal = egui::vec2(-direction.y, direction.x);
+        painter.add(Shape::convex_polygon(
+            vec![
+                tip,
+                tip - direction * size * 1.5 + normal * size * 0.6,
+                tip - direction * size * 1.5 - normal * size * 0.6,
+            ],
+            color,
+            Stroke::NONE,
+        ));
+    }
+}
+
+/// A scope, centered on us with forward at the top. A planet's blip sits at its position in our
+/// horizontal plane, on a stalk as long as it is high (or low).
+pub fn radar(
+    mut ctx: EguiContexts,
+    spacecraft_query: Query<(&Transform, &Spacecraft)>,
+    planet_query: PlanetBlips,
+    local_player: Res<LocalPlayer>,
+    config: Res<RadarConfig>,
+) {
+    if !config.show_radar {
+        return;
+    }
+    let Ok((transform, spacecraft)) = spacecraft_query.get_single() else {
+        return;
+    };
+    Window::new("Radar")
+        .title_bar(false)
+        .anchor(Align2::RIGHT_BOTTOM, [-10.0, -10.0])
+        .resizable(false)
+        .frame(Frame::window(&ctx.ctx_mut().style()).fill(Color32::from_black_alpha(160)))
+        .show(ctx.ctx_mut(), |ui| {
+            let scope_radius = 90.0;
+            let (response, painter) =
+                ui.allocate_painter(egui::Vec2::splat(scope_radius * 2.0), Sense::hover());
+            let center = response.rect.center();
+            let grid = Stroke::new(1.0, Color32::from_rgba_unmultiplied(0, 255, 0, 60));
+            painter.circle_stroke(center, scope_radius, grid);
+            painter.circle_stroke(center, scope_radius / 2.0, grid);
+            painter.line_segment(
+                [
+                    center - egui::vec2(scope_radius, 0.0),
+                    center + egui::vec2(scope_radius, 0.0),
+                ],
+                grid,
+            );
+            painter.line_segment(
+                [
+                    center - egui::vec2(0.0, scope_radius),
+                    center + egui::vec2(0.0, scope_radius),
+                ],
+                grid,
+            );
+
+            let scale = scope_radius / config.range;
+            let mut blips = planet_query
+                .iter()
+                .map(|(planet_transform, momentum, owner)| {
+                    let offset = planet_transform.translation - transform.translation;
+                    let local = transform.rotation.inverse() * offset;
+                    (local, offset, momentum, owner)
+                })
+                .collect::<Vec<_>>();
+            // Far before near, so nearer blips are drawn on top.
+            blips.sort_by(|a, b| b.0.length().total_cmp(&a.0.length()));
+            for (local, offset, momentum, owner) in blips {
+                let mut flat = egui::vec2(local.x, local.z) * scale;
+                let mut height = -local.y * scale;
+                let overflow = (flat.length() / scope_radius).max(1.0);
+                flat /= overflow;
+                height /= overflow;
+                let base = center + flat;
+                let blip = base + egui::vec2(0.0, height);
+                let color = blip_color(
+                    &config,
+                    owner,
+                    local_player.0,
+                    offset,
+                    momentum.velocity - spacecraft.velocity,
+                );
+                painter.line_segment([base, blip], Stroke::new(1.0, color.gamma_multiply(0.6)));
+                painter.circle_filled(blip, 1.5 + mass_to_radius(momentum.mass), color);
+            }
+            // Us.
+            painter.circle_filled(center, 2.0, Color32::WHITE);
+        });
+}
+
+fn blip_color(
+    config: &RadarConfig,
+    owner: &Owner,
+    local_player: Player,
+    offset: Vec3,
+    relative_velocity: Vec3,
+) -> Color32 {
+    match config.coloring {
+        RadarColoring::Ownership => match owner.player {
+            None => Color32::GRAY,
+            Some(player) if player == local_player => Color32::GREEN,
+            Some(player) => player_color(player),
+        },
+        RadarColoring::ApproachRate => {
+            let closing = -relative_velocity.dot(offset.normalize_or_zero());
+            // Fully saturated at this closing (or opening) speed.
+            let intensity = (closing.abs() / 5.0).min(1.0);
+            let toward = if closing > 0.0 {
+                Color32::RED
+            } else {
+                Color32::LIGHT_BLUE
+            };
+            lerp_color(Color32::GRAY, toward, intensity)
+        }
+    }
+}
+
+/// A color that stays the same for a given player from round to round.
+pub fn player_color(player: Player) -> Color32 {
+    const PALETTE: [Color32; 6] = [
+        Color32::RED,
+        Color32::LIGHT_BLUE,
+        Color32::YELLOW,
+        Color32::from_rgb(255, 0, 255),
+        Color32::from_rgb(255, 140, 0),
+        Color32::from_rgb(0, 255, 255),
+    ];
+    PALETTE[player.0 as usize % PALETTE.len()]
+}
+
+fn lerp_color(from: Color32, to: Color32, t: f32) -> Color32 {
+    let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    Color32::from_rgb(
+        channel(from.r(), to.r()),
+        channel(from.g(), to.g()),
+        channel(from.b(), to.b()),
+    )
+}
@@ -0,0 +1,695 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{Align2, Color32, Frame, RichText, Window},
+    EguiContexts,
+};
+use bevy_rapier3d::prelude::CollisionEvent;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use crate::appearance::Appearance;
+use crate::bindings::{read_actions, Action, ActionState};
+use crate::clock::SimulationClock;
+use crate::craft::move_projectiles;
+use crate::craft::{spawn_projectile, Projectile, ProjectileTarget, SpacecraftConfig};
+use crate::networking::is_local_authority;
+use crate::physics::{
+    handle_despawn_planet, handle_freefall, handle_planet_collisions, signal_freefall_delta,
+    size_planets, spawn_planet, transfer_planet_momentum, MergeAnimation, Momentum,
+    PlanetMergeEvent,
+};
+use crate::round::{mark_planet, MarkedPlanet};
+use crate::scoring::{Owner, Player, Score};
+use crate::{mass_to_radius, AppState, DespawnTimer};
+
+#[derive(Resource)]
+pub struct RewindConfig {
+    /// How much history is kept, in simulated time.
+    pub history: Duration,
+    /// Snapshots per second of simulated time.
+    pub tick_rate: f32,
+    /// How far back one rewind goes.
+    pub rewind: Duration,
+    /// The instant replay starts this long before the last merge...
+    pub replay_before: Duration,
+    /// ...and ends this long after it.
+    pub replay_after: Duration,
+}
+
+impl Default for RewindConfig {
+    fn default() -> Self {
+        Self {
+            history: Duration::from_secs(10),
+            tick_rate: 20.0,
+            rewind: Duration::from_secs(3),
+            replay_before: Duration::from_secs(3),
+            replay_after: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Keeps the last few seconds of the simulation, to rewind to or replay. Only where the
+/// simulation is ours: a client can't rewind the server.
+pub struct Rewinding;
+
+impl Plugin for Rewinding {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RewindConfig>()
+            .init_resource::<History>()
+            .add_systems(OnEnter(AppState::Countdown), clear_history)
+            .add_systems(
+                Update,
+                (
+                    rewind
+                        .after(read_actions)
+                        .before(signal_freefall_delta)
+                        .before(handle_planet_collisions)
+                        .before(transfer_planet_momentum)
+                        .before(move_projectiles),
+                    remember_merges.after(transfer_planet_momentum),
+                    record_history
+                        .after(handle_freefall)
+                        .after(size_planets)
+                        .after(handle_despawn_planet)
+                        .after(move_projectiles),
+                    replay_last_merge.after(read_actions),
+                )
+                    .run_if(in_state(AppState::Playing))
+                    .run_if(is_local_authority),
+            )
+            .add_systems(OnEnter(AppState::Replay), start_replay)
+            .add_systems(
+                Update,
+                (replay_banner, play_replay.after(replay_banner))
+                    .run_if(in_state(AppState::Replay)),
+            )
+            .add_systems(OnExit(AppState::Replay), end_replay);
+    }
+}
+
+#[derive(Debug, Clone)]
+struct PlanetState {
+    entity: Entity,
+    transform: Transform,
+    mass: f32,
+    velocity: Vec3,
+    appearance: Appearance,
+    owner: Owner,
+    marked: bool,
+}
+
+#[derive(Clone)]
+struct ProjectileState {
+    entity: Entity,
+    transform: Transform,
+    projectile: Projectile,
+    target: Option<ProjectileTarget>,
+    lifetime: Option<DespawnTimer>,
+}
+
+/// Every planet and projectile, and the score, at one tick of simulated time.
+#[derive(Clone)]
+pub struct Moment {
+    pub time: Duration,
+    planets: Vec<PlanetState>,
+    projectiles: Vec<ProjectileState>,
+    score: HashMap<Player, u32>,
+}
+
+impl Moment {
+    // Planets and projectiles that were respawned, and so are different entities now.
+    fn remap(&mut self, respawned: &HashMap<Entity, Entity>) {
+        let remapped = |entity: &mut Entity| {
+            if let Some(&now) = respawned.get(entity) {
+                *entity = now;
+            }
+        };
+        for planet in self.planets.iter_mut() {
+            remapped(&mut planet.entity);
+        }
+        for projectile in self.projectiles.iter_mut() {
+            remapped(&mut projectile.entity);
+            if let Some(target) = projectile.target.as_mut() {
+                remapped(&mut target.planet);
+            }
+        }
+    }
+}
+
+/// The last few seconds of moments, oldest first.
+#[derive(Resource, Default)]
+pub struct History {
+    moments: VecDeque<Moment>,
+    /// When the last merge happened, in simulated time, if it is still in the history.
+    pub last_merge: Option<Duration>,
+}
+
+impl History {
+    pub fn newest(&self) -> Option<&Moment> {
+        self.moments.back()
+    }
+
+    fn push(&mut self, moment: Moment, keep: Duration) {
+        while self
+            .moments
+            .front()
+            .is_some_and(|oldest| oldest.time + keep < moment.time)
+        {
+            self.moments.pop_front();
+        }
+        self.moments.push_back(moment);
+        let oldest = self.moments[0].time;
+        self.last_merge = self.last_merge.filter(|&merge| merge >= oldest);
+    }
+
+    // The newest moment from no later than `time`, or the oldest there is.
+    fn index_at(&self, time: Duration) -> Option<usize> {
+        if self.moments.is_empty() {
+            return None;
+        }
+        Some(
+            self.moments
+                .partition_point(|moment| moment.time <= time)
+                .saturating_sub(1),
+        )
+    }
+
+    // Forget everything after `index`: it didn't happen.
+    fn truncate_after(&mut self, index: usize) {
+        self.moments.truncate(index + 1);
+        let newest = self.moments[index].time;
+        self.last_merge = self.last_merge.filter(|&merge| merge <= newest);
+    }
+
+    fn remap(&mut self, respawned: &HashMap<Entity, Entity>) {
+        if respawned.is_empty() {
+            return;
+        }
+        for moment in self.moments.iter_mut() {
+            moment.remap(respawned);
+        }
+    }
+}
+
+type TimelinePlanets<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Transform,
+        &'static mut Momentum,
+        &'static mut Appearance,
+        &'static mut Owner,
+        Has<MarkedPlanet>,
+    ),
+    Without<Projectile>,
+>;
+
+type TimelineProjectiles<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Transform,
+        &'static mut Projectile,
+        Option<&'static ProjectileTarget>,
+        Option<&'static DespawnTimer>,
+    ),
+    Without<Momentum>,
+>;
+
+/// Everything a moment is taken from, and put back into.
+#[derive(SystemParam)]
+pub struct Timeline<'w, 's> {
+    commands: Commands<'w, 's>,
+    meshes: ResMut<'w, Assets<Mesh>>,
+    materials: ResMut<'w, Assets<StandardMaterial>>,
+    config: Res<'w, SpacecraftConfig>,
+    score: ResMut<'w, Score>,
+    collision_events: ResMut<'w, Events<CollisionEvent>>,
+    planet_query: TimelinePlanets<'w, 's>,
+    projectile_query: TimelineProjectiles<'w, 's>,
+}
+
+impl Timeline<'_, '_> {
+    pub fn capture(&self, time: Duration) -> Moment {
+        Moment {
+            time,
+            planets: self
+                .planet_query
+                .iter()
+                .map(
+                    |(entity, transform, momentum, appearance, owner, marked)| PlanetState {
+                        entity,
+                        transform: *transform,
+                        mass: momentum.mass,
+                        velocity: momentum.velocity,
+                        appearance: *appearance,
+                        owner: owner.clone(),
+                        marked,
+                    },
+                )
+                .collect(),
+            projectiles: self
+                .projectile_query
+                .iter()
+                .map(
+                    |(entity, transform, projectile, target, lifetime)| ProjectileState {
+                        entity,
+                        transform: *transform,
+                        projectile: projectile.clone(),
+                        target: target.copied(),
+                        lifetime: lifetime.cloned(),
+                    },
+                )
+                .collect(),
+            score: self.score.points.clone(),
+        }
+    }
+
+    /// Put every planet and projectile back as it was in `moment`. Whatever still exists is
+    /// moved back in place; whatever has gone since is spawned again. Returns whatever was
+    /// respawned, which are new entities, keyed by the entities they used to be.
+    pub fn restore(&mut self, moment: &Moment) -> HashMap<Entity, Entity> {
+        // Contacts from before the jump must not merge anything after it.
+        self.collision_events.clear();
+        self.score.points = moment.score.clone();
+
+        let mut respawned = HashMap::new();
+        let mut present: Vec<Entity> = self.planet_query.iter().map(|(e, ..)| e).collect();
+        for state in moment.planets.iter() {
+            present.retain(|&entity| entity != state.entity);
+            if let Ok((_, mut transform, mut momentum, mut appearance, mut owner, _)) =
+                self.planet_query.get_mut(state.entity)
+            {
+                *transform = state.transform;
+                momentum.mass = state.mass;
+                momentum.velocity = state.velocity;
+                // A new appearance is repainted, which is slow, so only if it is new.
+                if *appearance != state.appearance {
+                    *appearance = state.appearance;
+                }
+                *owner = state.owner.clone();
+                self.commands
+                    .entity(state.entity)
+                    .remove::<MergeAnimation>();
+            } else {
+                let planet = spawn_planet(
+                    mass_to_radius(state.mass),
+                    state.transform.translation,
+                    state.velocity,
+                    state.appearance,
+                    &mut self.commands,
+                    &mut self.meshes,
+                    &mut self.materials,
+                );
+                self.commands
+                    .entity(planet)
+                    .insert((state.transform, state.owner.clone()));
+                if state.marked {
+                    mark_planet(
+                        planet,
+                        &mut self.commands,
+                        &mut self.meshes,
+                        &mut self.materials,
+                    );
+                }
+                respawned.insert(state.entity, planet);
+            }
+        }
+        // Anything newer than the moment never happened.
+        for planet in present {
+            self.commands.entity(planet).despawn_recursive();
+        }
+
+        let mut present: Vec<Entity> = self.projectile_query.iter().map(|(e, ..)| e).collect();
+        for state in moment.projectiles.iter() {
+            present.retain(|&entity| entity != state.entity);
+            let projectile = match self.projectile_query.get_mut(state.entity) {
+                Ok((entity, mut transform, mut projectile, _, _)) => {
+                    *transform = state.transform;
+                    *projectile = state.projectile.clone();
+                    entity
+                }
+                Err(_) => {
+                    let projectile = spawn_projectile(
+                        &mut self.commands,
+                        &mut self.meshes,
+                        &mut self.materials,
+                        self.config.projectile_radius,
+                        state.transform.translation,
+                        state.projectile.clone(),
+                    );
+                    respawned.insert(state.entity, projectile);
+                    projectile
+                }
+            };
+            let mut projectile = self.commands.entity(projectile);
+            match state.target {
+                Some(mut target) => {
+                    if let Some(&planet) = respawned.get(&target.planet) {
+                        target.planet = planet;
+                    }
+                    projectile.insert(target);
+                }
+                None => {
+                    projectile.remove::<ProjectileTarget>();
+                }
+            }
+            match state.lifetime.clone() {
+                Some(lifetime) => {
+                    projectile.insert(lifetime);
+                }
+                None => {
+                    projectile.remove::<DespawnTimer>();
+                }
+            }
+        }
+        for projectile in present {
+            self.commands.entity(projectile).despawn_recursive();
+        }
+        respawned
+    }
+
+    // Partway from one moment to the next, for whatever is in both. Restore `from` first.
+    fn interpolate(&mut self, from: &Moment, to: &Moment, t: f32) {
+        for state in from.planets.iter() {
+            let Some(next) = to.planets.iter().find(|next| next.entity == state.entity) else {
+                continue;
+            };
+            if let Ok((_, mut transform, ..)) = self.planet_query.get_mut(state.entity) {
+                transform.translation = state
+                    .transform
+                    .translation
+                    .lerp(next.transform.translation, t);
+                transform.scale = state.transform.scale.lerp(next.transform.scale, t);
+            }
+        }
+        for state in from.projectiles.iter() {
+            let Some(next) = to
+                .projectiles
+                .iter()
+                .find(|next| next.entity == state.entity)
+            else {
+                continue;
+            };
+            if let Ok((_, mut transform, ..)) = self.projectile_query.get_mut(state.entity) {
+                transform.translation = state
+                    .transform
+                    .translation
+                    .lerp(next.transform.translation, t);
+            }
+        }
+    }
+}
+
+/// Records a moment every `1 / tick_rate` seconds of simulated time, after the simulation has
+/// moved on for the frame.
+pub fn record_history(
+    timeline: Timeline,
+    mut history: ResMut<History>,
+    clock: Res<SimulationClock>,
+    config: Res<RewindConfig>,
+) {
+    let now = clock.elapsed();
+    let tick = Duration::from_secs_f32(1.0 / config.tick_rate);
+    if history
+        .newest()
+        .is_some_and(|newest| now < newest.time + tick)
+    {
+        return;
+    }
+    history.push(timeline.capture(now), config.history);
+}
+
+/// A new round is a new history.
+pub fn clear_history(mut history: ResMut<History>) {
+    *history = History::default();
+}
+
+pub fn remember_merges(
+    mut merge_events: EventReader<PlanetMergeEvent>,
+    mut history: ResMut<History>,
+    clock: Res<SimulationClock>,
+) {
+    if merge_events.read().last().is_some() {
+        history.last_merge = Some(clock.elapsed());
+    }
+}
+
+/// Jumps back `RewindConfig::rewind` and carries on from there. Whatever happened since is
+/// forgotten.
+pub fn rewind(
+    actions: Res<ActionState>,
+    mut timeline: Timeline,
+    mut history: ResMut<History>,
+    mut clock: ResMut<SimulationClock>,
+    config: Res<RewindConfig>,
+) {
+    if !actions.just_pressed(Action::Rewind) {
+        return;
+    }
+    let Some(index) = history.index_at(clock.elapsed().saturating_sub(config.rewind)) else {
+        return;
+    };
+    history.truncate_after(index);
+    let moment = history.moments[index].clone();
+    info!(
+        "Rewinding from {:.1}s to {:.1}s",
+        clock.elapsed_seconds(),
+        moment.time.as_secs_f32()
+    );
+    let respawned = timeline.restore(&moment);
+    history.remap(&respawned);
+    clock.set_elapsed(moment.time);
+}
+
+pub fn replay_last_merge(
+    actions: Res<ActionState>,
+    history: Res<History>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if actions.just_pressed(Action::Replay) && history.last_merge.is_some() {
+        next_state.set(AppState::Replay);
+    }
+}
+
+#[derive(Resource)]
+pub struct Replay {
+    playhead: Duration,
+    end: Duration,
+    // The moment being played from, once it has been restored.
+    restored: Option<usize>,
+    // What to go back to afterwards.
+    present: Duration,
+    was_paused: bool,
+    pub skip: bool,
+}
+
+/// Keeps the present in the history, and stops the simulation while the past plays back.
+pub fn start_replay(
+    mut commands: Commands,
+    timeline: Timeline,
+    mut history: ResMut<History>,
+    mut clock: ResMut<SimulationClock>,
+    config: Res<RewindConfig>,
+) {
+    let present = clock.elapsed();
+    if history.newest().is_some_and(|newest| newest.time < present) {
+        history.push(timeline.capture(present), config.history);
+    }
+    let merge = history.last_merge.unwrap_or(present);
+    commands.insert_resource(Replay {
+        playhead: merge.saturating_sub(config.replay_before),
+        end: (merge + config.replay_after).min(present),
+        restored: None,
+        present,
+        was_paused: clock.paused,
+        skip: false,
+    });
+    clock.paused = true;
+}
+
+pub fn play_replay(
+    mut timeline: Timeline,
+    mut history: ResMut<History>,
+    mut replay: ResMut<Replay>,
+    mut clock: ResMut<SimulationClock>,
+    time: Res<Time>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if replay.skip || replay.playhead >= replay.end {
+        if let Some(index) = history.index_at(replay.present) {
+            let moment = history.moments[index].clone();
+            let respawned = timeline.restore(&moment);
+            history.remap(&respawned);
+        }
+        clock.set_elapsed(replay.present);
+        clock.paused = replay.was_paused;
+        next_state.set(AppState::Playing);
+        return;
+    }
+    let Some(index) = history.index_at(replay.playhead) else {
+        replay.skip = true;
+        return;
+    };
+    if replay.restored != Some(index) {
+        let moment = history.moments[index].clone();
+        let respawned = timeline.restore(&moment);
+        history.remap(&respawned);
+        replay.restored = Some(index);
+    } else if let Some(next) = history.moments.get(index + 1) {
+        let from = &history.moments[index];
+        let span = (next.time - from.time).as_secs_f32();
+        let t = (replay.playhead - from.time).as_secs_f32() / span;
+        timeline.interpolate(from, next, t.clamp(0.0, 1.0));
+    }
+    replay.playhead += time.delta();
+}
+
+pub fn replay_banner(mut ctx: EguiContexts, mut replay: ResMut<Replay>) {
+    let left = replay.end.saturating_sub(replay.playhead).as_secs_f32();
+    Window::new("Replay")
+        .title_bar(false)
+        .anchor(Align2::CENTER_TOP, [0.0, 10.0])
+        .resizable(false)
+        .frame(Frame::window(&ctx.ctx_mut().style()).fill(Color32::from_black_alpha(160)))
+        .show(ctx.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label(
+                    RichText::new(format!("INSTANT REPLAY  {left:.1}s"))
+                        .color(Color32::GREEN)
+                        .monospace(),
+                );
+                if ui.button("Skip").clicked() {
+                    replay.skip = true;
+                }
+            });
+        });
+}
+
+pub fn end_replay(mut commands: Commands) {
+    commands.remove_resource::<Replay>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::craft::ProjectileKind;
+
+    fn moment(millis: u64) -> Moment {
+        Moment {
+            time: Duration::from_millis(millis),
+            planets: vec![],
+            projectiles: vec![],
+            score: HashMap::new(),
+        }
+    }
+
+    fn history(millis: &[u64], keep: Duration) -> History {
+        let mut history = History::default();
+        for &millis in millis {
+            history.push(moment(millis), keep);
+        }
+        history
+    }
+
+    fn times(history: &History) -> Vec<u64> {
+        history
+            .moments
+            .iter()
+            .map(|moment| moment.time.as_millis() as u64)
+            .collect()
+    }
+
+    #[test]
+    fn history_keeps_exactly_the_configured_length() {
+        let keep = Duration::from_secs(1);
+        let mut history = history(&[0, 500, 1000], keep);
+        // The oldest moment is exactly `keep` old: still kept.
+        assert_eq!(times(&history), [0, 500, 1000]);
+
+        history.push(moment(1050), keep);
+        assert_eq!(times(&history), [500, 1000, 1050]);
+    }
+
+    #[test]
+    fn evicting_the_merge_forgets_it() {
+        let keep = Duration::from_secs(1);
+        let mut history = history(&[0, 500], keep);
+        history.last_merge = Some(Duration::from_millis(200));
+
+        history.push(moment(1000), keep);
+        assert_eq!(history.last_merge, Some(Duration::from_millis(200)));
+        history.push(moment(1400), keep);
+        assert_eq!(history.last_merge, None);
+    }
+
+    #[test]
+    fn index_at_finds_the_moment_no_later_than_time() {
+        let history = history(&[100, 150, 200], Duration::from_secs(1));
+        assert_eq!(History::default().index_at(Duration::ZERO), None);
+        // Before the oldest, the oldest is as far back as we can go.
+        assert_eq!(history.index_at(Duration::from_millis(50)), Some(0));
+        assert_eq!(history.index_at(Duration::from_millis(100)), Some(0));
+        assert_eq!(history.index_at(Duration::from_millis(149)), Some(0));
+        assert_eq!(history.index_at(Duration::from_millis(150)), Some(1));
+        assert_eq!(history.index_at(Duration::from_millis(999)), Some(2));
+    }
+
+    #[test]
+    fn truncating_forgets_later_merges() {
+        let mut history = history(&[100, 150, 200, 250], Duration::from_secs(1));
+        history.last_merge = Some(Duration::from_millis(150));
+        history.truncate_after(1);
+        assert_eq!(times(&history), [100, 150]);
+        assert_eq!(history.last_merge, Some(Duration::from_millis(150)));
+
+        history.last_merge = Some(Duration::from_millis(175));
+        history.truncate_after(0);
+        assert_eq!(times(&history), [100]);
+        assert_eq!(history.last_merge, None);
+    }
+
+    #[test]
+    fn remapping_follows_respawned_targets() {
+        let (projectile, planet, other) = (
+            Entity::from_raw(1),
+            Entity::from_raw(2),
+            Entity::from_raw(3),
+        );
+        let (new_projectile, new_planet) = (Entity::from_raw(11), Entity::from_raw(12));
+        let target = |planet| ProjectileState {
+            entity: projectile,
+            transform: Transform::IDENTITY,
+            projectile: Projectile {
+                kind: ProjectileKind::Push,
+                impulse: 1.0,
+                speed: 1.0,
+                velocity: Vec3::X,
+            },
+            target: Some(ProjectileTarget {
+                planet,
+                local_direction: Vec3::Y,
+            }),
+            lifetime: None,
+        };
+        let mut history = History::default();
+        let mut first = moment(0);
+        first.projectiles = vec![target(planet), target(other)];
+        history.push(first, Duration::from_secs(1));
+
+        history.remap(&HashMap::from([
+            (projectile, new_projectile),
+            (planet, new_planet),
+        ]));
+        let projectiles = &history.moments[0].projectiles;
+        assert!(projectiles.iter().all(|p| p.entity == new_projectile));
+        let targets: Vec<_> = projectiles
+            .iter()
+            .map(|p| p.target.unwrap().planet)
+            .collect();
+        assert_eq!(targets, [new_planet, other]);
+    }
+}
@@ -0,0 +1,411 @@
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{self, CentralPanel, Color32, Frame, RichText},
+    EguiContexts,
+};
+use rand::seq::SliceRandom;
+use std::fmt;
+use std::time::Duration;
+
+use crate::clock::SimulationClock;
+use crate::craft::{Projectile, Spacecraft, SpacecraftConfig, Weapon};
+use crate::helpscreen::styled_text_label;
+use crate::networking::is_local_authority;
+use crate::particles::{Particle, Shockwave};
+use crate::physics::{
+    handle_despawn_planet, handle_freefall, size_planets, transfer_planet_momentum, Momentum,
+    PlanetMergeEvent,
+};
+use crate::scoring::{Owner, Player, Score};
+use crate::AppState;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VictoryCondition {
+    /// Merging has left a single planet.
+    LastPlanetStanding,
+    /// Some planet has gathered at least this much mass.
+    TargetMass(f32),
+    /// The round simply ends. Highest score wins.
+    TimeLimit(Duration),
+    /// The two planets marked at the start of the round have merged.
+    MergeMarkedPair,
+}
+
+impl fmt::Display for VictoryCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VictoryCondition::LastPlanetStanding => write!(f, "Only one planet left"),
+            VictoryCondition::TargetMass(mass) => write!(f, "A planet reaches mass {mass:.0}"),
+            VictoryCondition::TimeLimit(limit) => write!(f, "{} seconds pass", limit.as_secs()),
+            VictoryCondition::MergeMarkedPair => write!(f, "The marked pair merges"),
+        }
+    }
+}
+
+/// The round ends as soon as any one of `victory_conditions` is met.
+#[derive(Resource)]
+pub struct RoundConfig {
+    pub victory_conditions: Vec<VictoryCondition>,
+    pub countdown: Duration,
+    // How long the final state of the round stays on screen before the results.
+    pub round_over: Duration,
+}
+
+impl Default for RoundConfig {
+    fn default() -> Self {
+        Self {
+            victory_conditions: vec![
+                VictoryCondition::LastPlanetStanding,
+                VictoryCondition::TimeLimit(Duration::from_secs(5 * 60)),
+            ],
+            countdown: Duration::from_secs(3),
+            round_over: Duration::from_secs(3),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RoundOutcome {
+    pub condition: VictoryCondition,
+    pub winner: Option<Player>,
+}
+
+#[derive(Resource, Default)]
+pub struct Round {
+    pub number: u32,
+    // Simulated time spent in `AppState::Playing` this round.
+    pub elapsed: Duration,
+    pub outcome: Option<RoundOutcome>,
+    timer: Timer,
+    // When play started, by the `SimulationClock`.
+    started: Option<Duration>,
+}
+
+/// One of the two planets that must be merged under `VictoryCondition::MergeMarkedPair`.
+#[derive(Component)]
+pub struct MarkedPlanet;
+
+/// Hovers over a marked planet. Not a child of it: planets are scaled to their radius, and the
+/// beacon shouldn't grow with them.
+#[derive(Component)]
+pub struct Beacon {
+    pub planet: Entity,
+}
+
+// How far above the planet's surface its beacon hovers.
+const BEACON_CLEARANCE: f32 = 1.5;
+
+pub struct Rounds;
+
+impl Plugin for Rounds {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RoundConfig>()
+            .init_resource::<Round>()
+            .add_systems(Update, main_menu.run_if(in_state(AppState::MainMenu)))
+            .add_systems(OnEnter(AppState::Countdown), start_round)
+            .add_systems(
+                Update,
+                (mark_pair, countdown).run_if(in_state(AppState::Countdown)),
+            )
+            .add_systems(
+                Update,
+                follow_marked_planets
+                    .after(handle_freefall)
+                    .after(size_planets),
+            )
+            .add_systems(
+                Update,
+                // Between the merge and the minor planet's despawn, so both are still marked.
+                check_victory
+                    .after(transfer_planet_momentum)
+                    .before(handle_despawn_planet)
+                    .run_if(in_state(AppState::Playing))
+                    .run_if(is_local_authority),
+            )
+            .add_systems(OnEnter(AppState::RoundOver), start_round_over_timer)
+            .add_systems(Update, round_over.run_if(in_state(AppState::RoundOver)))
+            .add_systems(OnEnter(AppState::Results), teardown)
+            .add_systems(Update, results.run_if(in_state(AppState::Results)));
+    }
+}
+
+pub fn start_round(
+    mut round: ResMut<Round>,
+    mut score: ResMut<Score>,
+    config: Res<RoundConfig>,
+    spacecraft_config: Res<SpacecraftConfig>,
+    mut spacecraft_query: Query<(&mut Transform, &mut Spacecraft, &mut Weapon)>,
+) {
+    round.number += 1;
+    round.elapsed = Duration::ZERO;
+    round.started = None;
+    round.outcome = None;
+    round.timer = Timer::new(config.countdown, TimerMode::Once);
+    score.points.clear();
+    for (mut transform, mut spacecraft, mut weapon) in spacecraft_query.iter_mut() {
+        *transform = spacecraft_config.start_transform;
+        *spacecraft = Spacecraft::new(&spacecraft_config);
+        *weapon = Weapon::new(weapon.kind);
+    }
+    debug!("Starting round {}", round.number);
+}
+
+// Planets are spawned on entering `Countdown`, so they show up here a frame later.
+pub fn mark_pair(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    config: Res<RoundConfig>,
+    planet_query: Query<Entity, With<Momentum>>,
+    marked_query: Query<(), With<MarkedPlanet>>,
+) {
+    if !config
+        .victory_conditions
+        .contains(&VictoryCondition::MergeMarkedPair)
+        || !marked_query.is_empty()
+    {
+        return;
+    }
+    let planets = planet_query.iter().collect::<Vec<_>>();
+    for &planet in planets.choose_multiple(&mut rand::thread_rng(), 2) {
+        debug!("Marking planet {planet:?}");
+        mark_planet(planet, &mut commands, &mut meshes, &mut materials);
+    }
+}
+
+/// Make `planet` one of the marked pair, with a beacon over it.
+pub fn mark_planet(
+    planet: Entity,
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+) {
+    commands.entity(planet).insert(MarkedPlanet);
+    commands
+        .spawn(PbrBundle {
+            mesh: meshes.add(
+                Mesh::try_from(shape::Icosphere {
+                    radius: 0.3,
+                    ..default()
+                })
+                .unwrap(),
+            ),
+            material: materials.add(StandardMaterial {
+                emissive: Color::FUCHSIA * 10.0,
+                ..default()
+            }),
+            ..default()
+        })
+        .insert(Beacon { planet });
+}
+
+pub fn follow_marked_planets(
+    mut commands: Commands,
+    mut beacon_query: Query<(Entity, &Beacon, &mut Transform)>,
+    planet_query: Query<&Transform, (With<MarkedPlanet>, Without<Beacon>)>,
+) {
+    for (entity, beacon, mut transform) in beacon_query.iter_mut() {
+        let Ok(planet) = planet_query.get(beacon.planet) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+        // A planet's scale is its radius.
+        transform.translation = planet.translation + Vec3::Y * (planet.scale.y + BEACON_CLEARANCE);
+    }
+}
+
+pub fn countdown(
+    mut ctx: EguiContexts,
+    mut round: ResMut<Round>,
+    mut next_state: ResMut<NextState<AppState>>,
+    time: Res<Time>,
+) {
+    round.timer.tick(time.delta());
+    if round.timer.finished() {
+        next_state.set(AppState::Playing);
+    }
+    let remaining = round.timer.remaining_secs().ceil();
+    CentralPanel::default()
+        .frame(Frame::none())
+        .show(ctx.ctx_mut(), |ui| {
+            ui.vertical_centered(|ui| {
+                ui.add_space(ui.available_height() / 3.0);
+                styled_text_label(50.0, ui, &format!("ROUND {}", round.number));
+                styled_text_label(80.0, ui, &format!("{remaining}"));
+            });
+        });
+}
+
+pub fn check_victory(
+    mut round: ResMut<Round>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut merge_events: EventReader<PlanetMergeEvent>,
+    planet_query: Query<(&Momentum, &Owner, Has<MarkedPlanet>)>,
+    config: Res<RoundConfig>,
+    score: Res<Score>,
+    clock: Res<SimulationClock>,
+) {
+    // By the simulation's clock, so time doesn't run out while it is paused, slowed or rewound.
+    let now = clock.elapsed();
+    let started = *round
+        .started
+        .get_or_insert(now.saturating_sub(clock.delta()));
+    round.elapsed = now.saturating_sub(started);
+    let is_marked = |planet| matches!(planet_query.get(planet), Ok((_, _, true)));
+    let marked_merge = merge_events
+        .read()
+        .find(|event| is_marked(event.major) && is_marked(event.minor));
+    let leader = score
+        .points
+        .iter()
+        .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))
+        .map(|(player, _)| *player);
+    for &condition in config.victory_conditions.iter() {
+        let winner = match condition {
+            VictoryCondition::LastPlanetStanding => {
+                match planet_query.iter().collect::<Vec<_>>()[..] {
+                    [(_, owner, _)] => Some(owner.player),
+                    _ => None,
+                }
+            }
+            VictoryCondition::TargetMass(target) => planet_query
+                .iter()
+                .find(|(momentum, _, _)| momentum.mass >= target)
+                .map(|(_, owner, _)| owner.player),
+            VictoryCondition::TimeLimit(limit) => (round.elapsed >= limit).then_some(leader),
+            VictoryCondition::MergeMarkedPair => marked_merge.map(|event| event.owner),
+        };
+        if let Some(winner) = winner {
+            debug!(
+                "Round {} is over: {condition}, won by {winner:?}",
+                round.number
+            );
+            round.outcome = Some(RoundOutcome { condition, winner });
+            next_state.set(AppState::RoundOver);
+            return;
+        }
+    }
+}
+
+pub fn start_round_over_timer(mut round: ResMut<Round>, config: Res<RoundConfig>) {
+    round.timer = Timer::new(config.round_over, TimerMode::Once);
+}
+
+pub fn round_over(
+    mut ctx: EguiContexts,
+    mut round: ResMut<Round>,
+    mut next_state: ResMut<NextState<AppState>>,
+    time: Res<Time>,
+) {
+    round.timer.tick(time.delta());
+    if round.timer.finished() {
+        next_state.set(AppState::Results);
+    }
+    CentralPanel::default()
+        .frame(Frame::none())
+        .show(ctx.ctx_mut(), |ui| {
+            ui.vertical_centered(|ui| {
+                ui.add_space(ui.available_height() / 3.0);
+                styled_text_label(50.0, ui, "ROUND OVER");
+                if let Some(outcome) = round.outcome {
+                    styled_text_label(22.0, ui, &outcome.condition.to_string());
+                }
+            });
+        });
+}
+
+// Everything a round leaves behind.
+type RoundDebris = Or<(
+    With<Momentum>,
+    With<Projectile>,
+    With<Shockwave>,
+    With<Beacon>,
+)>;
+
+/// Clear the stage for the next round.
+pub fn teardown(
+    mut commands: Commands,
+    debris_query: Query<Entity, RoundDebris>,
+    mut particle_query: Query<(&mut Particle, &mut Visibility)>,
+) {
+    for entity in debris_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    // Particles belong to the pool. They only go back in it.
+    for (mut particle, mut visibility) in particle_query.iter_mut() {
+        if particle.is_live() {
+            particle.retire();
+            *visibility = Visibility::Hidden;
+        }
+    }
+}
+
+pub fn main_menu(
+    mut ctx: EguiContexts,
+    mut next_state: ResMut<NextState<AppState>>,
+    config: Res<RoundConfig>,
+) {
+    menu_panel(&mut ctx, |ui| {
+        styled_text_label(50.0, ui, "MASS GATHERING");
+        ui.add_space(30.0);
+        styled_text_label(22.0, ui, ".. The round ends when ..");
+        for condition in config.victory_conditions.iter() {
+            styled_text_label(18.0, ui, &condition.to_string());
+        }
+        ui.add_space(30.0);
+        if menu_button(ui, "START") {
+            next_state.set(AppState::Countdown);
+        }
+    });
+}
+
+pub fn results(
+    mut ctx: EguiContexts,
+    mut next_state: ResMut<NextState<AppState>>,
+    round: Res<Round>,
+    score: Res<Score>,
+) {
+    let mut standings = score.points.iter().collect::<Vec<_>>();
+    standings.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    menu_panel(&mut ctx, |ui| {
+        styled_text_label(50.0, ui, &format!("ROUND {} RESULTS", round.number));
+        if let Some(outcome) = round.outcome {
+            styled_text_label(22.0, ui, &outcome.condition.to_string());
+            let winner = match outcome.winner {
+                Some(Player(player)) => format!("Player {player} wins!"),
+                None => "Nobody wins.".to_string(),
+            };
+            styled_text_label(22.0, ui, &winner);
+        }
+        ui.add_space(30.0);
+        for (player, points) in standings {
+            styled_text_label(18.0, ui, &format!("Player {}: {points}", player.0));
+        }
+        ui.add_space(30.0);
+        if menu_button(ui, "PLAY AGAIN") {
+            next_state.set(AppState::Countdown);
+        }
+        if menu_button(ui, "MAIN MENU") {
+            next_state.set(AppState::MainMenu);
+        }
+    });
+}
+
+fn menu_panel(ctx: &mut EguiContexts, add_contents: impl FnOnce(&mut egui::Ui)) {
+    CentralPanel::default()
+        .frame(Frame {
+            fill: Color32::from_rgba_premultiplied(0, 0, 0, 240),
+            ..Default::default()
+        })
+        .show(ctx.ctx_mut(), |ui| {
+            ui.vertical_centered(|ui| {
+                ui.add_space(100.0);
+                add_contents(ui);
+            });
+        });
+}
+
+fn menu_button(ui: &mut egui::Ui, text: &str) -> bool {
+    ui.button(RichText::new(text).color(Color32::GREEN).size(30.0))
+        .clicked()
+}
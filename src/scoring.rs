@@ -0,0 +1,307 @@
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{Align2, Color32, Frame, Window},
+    EguiContexts,
+};
+use std::collections::HashMap;
+
+use crate::craft::ProjectileCollisionEvent;
+use crate::networking::is_local_authority;
+use crate::physics::PlanetMergeEvent;
+
+/// Somebody who fires projectiles. Networked players are numbered by the server.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Player(pub u32);
+
+/// Which player is sitting at this keyboard.
+#[derive(Resource, Debug, Default, PartialEq)]
+pub struct LocalPlayer(pub Player);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OwnershipRule {
+    /// A planet belongs to whoever hit it last. In a merge, the major planet's owner wins.
+    #[default]
+    LastHit,
+    /// A planet belongs to whoever has delivered it the most impulse. Merges pool the tallies.
+    MajorityImpulse,
+}
+
+#[derive(Resource)]
+pub struct ScoringConfig {
+    pub ownership_rule: OwnershipRule,
+    pub show_scoreboard: bool,
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        Self {
+            ownership_rule: OwnershipRule::LastHit,
+            show_scoreboard: true,
+        }
+    }
+}
+
+/// Every planet starts out unowned.
+#[derive(Component, Debug, Default, Clone)]
+pub struct Owner {
+    pub player: Option<Player>,
+    // Total impulse each player has delivered to this planet, or to planets merged into it.
+    pub impulse: HashMap<Player, f32>,
+}
+
+impl Owner {
+    pub fn record_hit(&mut self, player: Player, impulse: f32, rule: OwnershipRule) {
+        *self.impulse.entry(player).or_default() += impulse;
+        self.player = match rule {
+            OwnershipRule::LastHit => Some(player),
+            OwnershipRule::MajorityImpulse => self.majority(),
+        };
+    }
+
+    /// Fold the minor planet's ownership into this, the major planet's.
+    pub fn absorb(&mut self, minor: &Owner, rule: OwnershipRule) {
+        for (player, impulse) in minor.impulse.iter() {
+            *self.impulse.entry(*player).or_default() += impulse;
+        }
+        self.player = match rule {
+            OwnershipRule::LastHit => self.player.or(minor.player),
+            OwnershipRule::MajorityImpulse => self.majority(),
+        };
+    }
+
+    // Ties go to the current owner, then to the lowest numbered player.
+    fn majority(&self) -> Option<Player> {
+        let (&leader, &most) = self
+            .impulse
+            .iter()
+            .max_by(|a, b| a.1.total_cmp(b.1).then(b.0.cmp(a.0)))?;
+        match self.player {
+            Some(current) if self.impulse.get(&current) == Some(&most) => Some(current),
+            _ => Some(leader),
+        }
+    }
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct Score {
+    pub points: HashMap<Player, u32>,
+}
+
+/// A player's projectile struck a planet.
+#[derive(Debug, Event)]
+pub struct PlanetHitEvent {
+    pub planet: Entity,
+    pub player: Player,
+    pub impulse: f32,
+}
+
+#[derive(Debug, Event)]
+pub struct OwnershipChangedEvent {
+    pub planet: Entity,
+    pub previous: Option<Player>,
+    pub owner: Option<Player>,
+}
+
+#[derive(Debug, Event)]
+pub struct ScoreEvent {
+    pub player: Player,
+    pub points: u32,
+    // The planet that survived the merge that earned the points.
+    pub planet: Entity,
+}
+
+pub struct Scoring;
+
+impl Plugin for Scoring {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScoringConfig>()
+            .init_resource::<Score>()
+            .init_resource::<LocalPlayer>()
+            .add_event::<PlanetHitEvent>()
+            .add_event::<OwnershipChangedEvent>()
+            .add_event::<ScoreEvent>()
+            .add_systems(
+                Update,
+                (
+                    signal_planet_hits,
+                    record_ownership.after(signal_planet_hits),
+                    // A client is sent the score, merges and all.
+                    award_merge_points.run_if(is_local_authority),
+                ),
+            );
+    }
+}
+
+// Only our own projectiles are simulated here. Other players' hits arrive over the network.
+pub fn signal_planet_hits(
+    mut projectile_events: EventReader<ProjectileCollisionEvent>,
+    mut hit_events: EventWriter<PlanetHitEvent>,
+    local_player: Res<LocalPlayer>,
+) {
+    for event in projectile_events.read() {
+        hit_events.send(PlanetHitEvent {
+            planet: event.planet,
+            player: local_player.0,
+            impulse: event.impulse,
+        });
+    }
+}
+
+pub fn record_ownership(
+    mut planet_query: Query<&mut Owner>,
+    mut hit_events: EventReader<PlanetHitEvent>,
+    mut ownership_events: EventWriter<OwnershipChangedEvent>,
+    config: Res<ScoringConfig>,
+) {
+    for event in hit_events.read() {
+        if let Ok(mut owner) = planet_query.get_mut(event.planet) {
+            let previous = owner.player;
+            owner.record_hit(event.player, event.impulse, config.ownership_rule);
+            if owner.player != previous {
+                debug!(
+                    "Planet {:?} changed hands: {previous:?} -> {:?}",
+                    event.planet, owner.player
+                );
+                ownership_events.send(OwnershipChangedEvent {
+                    planet: event.planet,
+                    previous,
+                    owner: owner.player,
+                });
+            }
+        }
+    }
+}
+
+/// Whoever owns the planet that results from a merge caused it, and is paid by absorbed mass.
+pub fn award_merge_points(
+    mut score: ResMut<Score>,
+    mut merge_events: EventReader<PlanetMergeEvent>,
+    mut score_events: EventWriter<ScoreEvent>,
+) {
+    for event in merge_events.read() {
+        if let Some(player) = event.owner {
+            let points = event.absorbed_mass.ceil().max(1.0) as u32;
+            *score.points.entry(player).or_default() += points;
+            debug!(
+                "{player:?} scores {points} for the merge into {:?}",
+                event.major
+            );
+            score_events.send(ScoreEvent {
+                player,
+                points,
+                planet: event.major,
+            });
+        }
+    }
+}
+
+pub fn scoreboard(
+    mut ctx: EguiContexts,
+    score: Res<Score>,
+    local_player: Res<LocalPlayer>,
+    config: Res<ScoringConfig>,
+) {
+    if !config.show_scoreboard {
+        return;
+    }
+    let mut standings = score.points.iter().collect::<Vec<_>>();
+    standings.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    Window::new("Scoreboard")
+        .anchor(Align2::RIGHT_TOP, [-10.0, 10.0])
+        .resizable(false)
+        .collapsible(true)
+        .frame(Frame::window(&ctx.ctx_mut().style()).fill(Color32::from_black_alpha(160)))
+        .show(ctx.ctx_mut(), |ui| {
+            if standings.is_empty() {
+                ui.colored_label(Color32::GREEN, "No merges yet");
+            }
+            for (player, points) in standings {
+                let you = if *player == local_player.0 {
+                    " (you)"
+                } else {
+                    ""
+                };
+                ui.colored_label(
+                    Color32::GREEN,
+                    format!("Player {}{you}: {points}", player.0),
+                );
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use OwnershipRule::{LastHit, MajorityImpulse};
+
+    const ALICE: Player = Player(1);
+    const BOB: Player = Player(2);
+
+    #[test]
+    fn last_hit_changes_owner_every_hit() {
+        let mut owner = Owner::default();
+        owner.record_hit(ALICE, 10.0, LastHit);
+        assert_eq!(owner.player, Some(ALICE));
+        owner.record_hit(BOB, 1.0, LastHit);
+        assert_eq!(owner.player, Some(BOB));
+    }
+
+    #[test]
+    fn majority_changes_owner_once_overtaken() {
+        let mut owner = Owner::default();
+        owner.record_hit(ALICE, 5.0, MajorityImpulse);
+        owner.record_hit(BOB, 3.0, MajorityImpulse);
+        assert_eq!(owner.player, Some(ALICE));
+        owner.record_hit(BOB, 3.0, MajorityImpulse);
+        assert_eq!(owner.player, Some(BOB));
+    }
+
+    #[test]
+    fn majority_ties_go_to_the_owner_then_the_lowest_player() {
+        let mut owner = Owner::default();
+        owner.record_hit(BOB, 5.0, MajorityImpulse);
+        owner.record_hit(ALICE, 5.0, MajorityImpulse);
+        assert_eq!(owner.player, Some(BOB));
+
+        let mut unowned = Owner {
+            player: None,
+            impulse: HashMap::from([(BOB, 5.0), (ALICE, 5.0)]),
+        };
+        unowned.record_hit(ALICE, 0.0, MajorityImpulse);
+        assert_eq!(unowned.player, Some(ALICE));
+    }
+
+    #[test]
+    fn absorbing_carries_impulse_over() {
+        let mut major = Owner::default();
+        major.record_hit(ALICE, 4.0, MajorityImpulse);
+        let mut minor = Owner::default();
+        minor.record_hit(BOB, 3.0, MajorityImpulse);
+        minor.record_hit(ALICE, 1.0, MajorityImpulse);
+
+        major.absorb(&minor, MajorityImpulse);
+        assert_eq!(major.impulse[&ALICE], 5.0);
+        assert_eq!(major.impulse[&BOB], 3.0);
+        assert_eq!(major.player, Some(ALICE));
+
+        // Bob's impulse is carried over, so a little more puts him ahead.
+        major.record_hit(BOB, 2.5, MajorityImpulse);
+        assert_eq!(major.player, Some(BOB));
+    }
+
+    #[test]
+    fn last_hit_absorb_keeps_the_major_owner() {
+        let mut minor = Owner::default();
+        minor.record_hit(BOB, 1.0, LastHit);
+
+        let mut unowned = Owner::default();
+        unowned.absorb(&minor, LastHit);
+        assert_eq!(unowned.player, Some(BOB));
+
+        let mut major = Owner::default();
+        major.record_hit(ALICE, 1.0, LastHit);
+        major.absorb(&minor, LastHit);
+        assert_eq!(major.player, Some(ALICE));
+        assert_eq!(major.impulse[&BOB], 1.0);
+    }
+}
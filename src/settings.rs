@@ -0,0 +1,472 @@
+use bevy::core_pipeline::Skybox;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{Align2, Color32, ComboBox, DragValue, Grid, RichText, Window},
+    EguiContexts,
+};
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::appearance::{Appearance, AppearanceConfig};
+use crate::craft::{SpacecraftConfig, StereoMode};
+use crate::physics::PhysicsConfig;
+use crate::sky::{skybox_image, SkyConfig};
+use crate::{config_dir, AppState};
+
+const SETTINGS_FILE: &str = "settings.cfg";
+
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum GraphicsQuality {
+    /// No antialiasing, small planet textures, no clouds and a blurry sky.
+    Low,
+    #[default]
+    Medium,
+    /// Big planet textures and a sharp sky. Slower to generate, so merges may stutter.
+    High,
+}
+
+impl GraphicsQuality {
+    pub const ALL: [GraphicsQuality; 3] = [
+        GraphicsQuality::Low,
+        GraphicsQuality::Medium,
+        GraphicsQuality::High,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            GraphicsQuality::Low => "low",
+            GraphicsQuality::Medium => "medium",
+            GraphicsQuality::High => "high",
+        }
+    }
+
+    fn msaa(&self) -> Msaa {
+        match self {
+            GraphicsQuality::Low => Msaa::Off,
+            GraphicsQuality::Medium | GraphicsQuality::High => Msaa::Sample4,
+        }
+    }
+
+    fn texture_width(&self) -> u32 {
+        match self {
+            GraphicsQuality::Low => 128,
+            GraphicsQuality::Medium => 256,
+            GraphicsQuality::High => 512,
+        }
+    }
+
+    fn clouds(&self) -> bool {
+        *self != GraphicsQuality::Low
+    }
+
+    fn sky_resolution(&self) -> u32 {
+        match self {
+            GraphicsQuality::Low => 256,
+            GraphicsQuality::Medium => 512,
+            GraphicsQuality::High => 1024,
+        }
+    }
+}
+
+impl fmt::Display for GraphicsQuality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphicsQuality::Low => write!(f, "Low"),
+            GraphicsQuality::Medium => write!(f, "Medium"),
+            GraphicsQuality::High => write!(f, "High"),
+        }
+    }
+}
+
+/// Everything on the settings page, which is everything in the settings file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub sims_per_frame: u8,
+    pub trails: bool,
+    pub impact_scale: f32,
+    pub recoil: f32,
+    pub projectile_radius: f32,
+    pub stereo_mode: StereoMode,
+    pub stereo_iod: f32,
+    pub stereo_convergence: f32,
+    pub graphics_quality: GraphicsQuality,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::gather(
+            &PhysicsConfig::default(),
+            &SpacecraftConfig::default(),
+            GraphicsQuality::default(),
+        )
+    }
+}
+
+impl Settings {
+    pub fn gather(
+        physics: &PhysicsConfig,
+        spacecraft: &SpacecraftConfig,
+        graphics_quality: GraphicsQuality,
+    ) -> Self {
+        Self {
+            sims_per_frame: physics.sims_per_frame,
+            trails: physics.trails,
+            impact_scale: spacecraft.impact_scale,
+            recoil: spacecraft.recoil,
+            projectile_radius: spacecraft.projectile_radius,
+            stereo_mode: spacecraft.stereo_mode,
+            stereo_iod: spacecraft.stereo_iod,
+            stereo_convergence: spacecraft.stereo_convergence,
+            graphics_quality,
+        }
+    }
+
+    /// Everything but the graphics quality, which `Graphics::apply` takes care of.
+    pub fn apply(&self, physics: &mut PhysicsConfig, spacecraft: &mut SpacecraftConfig) {
+        physics.sims_per_frame = self.sims_per_frame;
+        physics.trails = self.trails;
+        spacecraft.impact_scale = self.impact_scale;
+        spacecraft.recoil = self.recoil;
+        spacecraft.projectile_radius = self.projectile_radius;
+        spacecraft.stereo_mode = self.stereo_mode;
+        spacecraft.stereo_iod = self.stereo_iod;
+        spacecraft.stereo_convergence = self.stereo_convergence;
+    }
+
+    pub fn path() -> PathBuf {
+        config_dir().join(SETTINGS_FILE)
+    }
+
+    /// Read the user's settings over `self`, which stands in for anything they have not saved.
+    pub fn load(mut self) -> Self {
+        let path = Self::path();
+        if let Ok(text) = std::fs::read_to_string(&path) {
+            info!("Loading settings from {path:?}");
+            self.parse(&text);
+        }
+        self
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        info!("Saving settings to {path:?}");
+        std::fs::write(path, self.to_string())
+    }
+
+    /// One setting per line: `<setting> <value>`. Lines that do not parse are skipped with a
+    /// warning, and settings that are not mentioned are left as they are.
+    pub fn parse(&mut self, text: &str) {
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if self.parse_line(line).is_none() {
+                warn!("Ignoring setting on line {}: {line:?}", number + 1);
+            }
+        }
+    }
+
+    fn parse_line(&mut self, line: &str) -> Option<()> {
+        let (name, value) = line.split_once(char::is_whitespace)?;
+        let value = value.trim();
+        match name {
+            "sims_per_frame" => self.sims_per_frame = value.parse().ok()?,
+            "trails" => self.trails = value.parse().ok()?,
+            "impact_scale" => self.impact_scale = value.parse().ok()?,
+            "recoil" => self.recoil = value.parse().ok()?,
+            "projectile_radius" => self.projectile_radius = value.parse().ok()?,
+            "stereo_mode" => {
+                self.stereo_mode = stereo_modes().find(|mode| format!("{mode:?}") == value)?
+            }
+            "stereo_iod" => self.stereo_iod = value.parse().ok()?,
+            "stereo_convergence" => self.stereo_convergence = value.parse().ok()?,
+            "graphics_quality" => {
+                self.graphics_quality = GraphicsQuality::ALL
+                    .into_iter()
+                    .find(|quality| quality.name() == value)?
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+}
+
+impl fmt::Display for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# <setting> <value>")?;
+        writeln!(f, "sims_per_frame {}", self.sims_per_frame)?;
+        writeln!(f, "trails {}", self.trails)?;
+        writeln!(f, "impact_scale {}", self.impact_scale)?;
+        writeln!(f, "recoil {}", self.recoil)?;
+        writeln!(f, "projectile_radius {}", self.projectile_radius)?;
+        writeln!(f, "stereo_mode {:?}", self.stereo_mode)?;
+        writeln!(f, "stereo_iod {}", self.stereo_iod)?;
+        writeln!(f, "stereo_convergence {}", self.stereo_convergence)?;
+        writeln!(f, "graphics_quality {}", self.graphics_quality.name())
+    }
+}
+
+// Every stereo mode, in the order the stereo key cycles through them.
+fn stereo_modes() -> impl Iterator<Item = StereoMode> {
+    std::iter::successors(Some(StereoMode::Off), |mode| {
+        Some(mode.next()).filter(|&next| next != StereoMode::Off)
+    })
+}
+
+/// Whatever a change in graphics quality has to touch.
+#[derive(SystemParam)]
+pub struct Graphics<'w, 's> {
+    msaa: ResMut<'w, Msaa>,
+    appearance: ResMut<'w, AppearanceConfig>,
+    sky: ResMut<'w, SkyConfig>,
+    images: ResMut<'w, Assets<Image>>,
+    skybox_query: Query<'w, 's, &'static Skybox>,
+    planet_query: Query<'w, 's, &'static mut Appearance>,
+}
+
+impl Graphics<'_, '_> {
+    /// Only regenerates what `quality` actually changes: the sky and the planets take a while.
+    pub fn apply(&mut self, quality: GraphicsQuality) {
+        if *self.msaa != quality.msaa() {
+            *self.msaa = quality.msaa();
+        }
+        if self.sky.resolution != quality.sky_resolution() {
+            self.sky.resolution = quality.sky_resolution();
+            // Both eyes share one sky.
+            let mut skyboxes: Vec<_> = self
+                .skybox_query
+                .iter()
+                .map(|skybox| skybox.0.id())
+                .collect();
+            skyboxes.dedup();
+            if !skyboxes.is_empty() {
+                let image = skybox_image(&self.sky);
+                for skybox in skyboxes {
+                    self.images.insert(skybox, image.clone());
+                }
+            }
+        }
+        if self.appearance.texture_width != quality.texture_width()
+            || self.appearance.clouds != quality.clouds()
+        {
+            self.appearance.texture_width = quality.texture_width();
+            self.appearance.clouds = quality.clouds();
+            // `paint_planets` repaints whatever has changed.
+            for mut appearance in self.planet_query.iter_mut() {
+                appearance.set_changed();
+            }
+        }
+    }
+}
+
+/// Runs before anything is set up, so a saved graphics quality is generated once, not twice.
+pub fn load_settings(
+    mut physics: ResMut<PhysicsConfig>,
+    mut spacecraft: ResMut<SpacecraftConfig>,
+    mut quality: ResMut<GraphicsQuality>,
+    mut graphics: Graphics,
+) {
+    let current = Settings::gather(&physics, &spacecraft, *quality);
+    let settings = current.clone().load();
+    if settings != current {
+        settings.apply(&mut physics, &mut spacecraft);
+        if settings.graphics_quality != *quality {
+            *quality = settings.graphics_quality;
+            graphics.apply(*quality);
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct SettingsPage {
+    status: String,
+}
+
+/// Changes take effect as soon as they are made. Saving keeps them for next time.
+pub fn settings_page(
+    mut ctx: EguiContexts,
+    mut page: ResMut<SettingsPage>,
+    mut physics: ResMut<PhysicsConfig>,
+    mut spacecraft: ResMut<SpacecraftConfig>,
+    mut quality: ResMut<GraphicsQuality>,
+    mut graphics: Graphics,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let current = Settings::gather(&physics, &spacecraft, *quality);
+    let mut settings = current.clone();
+    Window::new("Settings")
+        .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx.ctx_mut(), |ui| {
+            ui.heading(RichText::new("Simulation").color(Color32::GREEN));
+            Grid::new("simulation").show(ui, |ui| {
+                ui.label("Steps per frame");
+                ui.add(DragValue::new(&mut settings.sims_per_frame).clamp_range(1..=50));
+                ui.end_row();
+                ui.label("Trails");
+                ui.checkbox(&mut settings.trails, "");
+                ui.end_row();
+            });
+            ui.separator();
+
+            ui.heading(RichText::new("Weapons").color(Color32::GREEN));
+            Grid::new("weapons").show(ui, |ui| {
+                ui.label("Impact scale");
+                ui.add(
+                    DragValue::new(&mut settings.impact_scale)
+                        .speed(0.01)
+                        .clamp_range(0.0..=10.0)
+                        .suffix("x"),
+                );
+                ui.end_row();
+                ui.label("Recoil");
+                ui.add(
+                    DragValue::new(&mut settings.recoil)
+                        .speed(0.001)
+                        .clamp_range(0.0..=1.0),
+                );
+                ui.end_row();
+                ui.label("Projectile radius");
+                ui.add(
+                    DragValue::new(&mut settings.projectile_radius)
+                        .speed(0.005)
+                        .clamp_range(0.01..=1.0),
+                );
+                ui.end_row();
+            });
+            ui.separator();
+
+            ui.heading(RichText::new("Stereo").color(Color32::GREEN));
+            Grid::new("stereo").show(ui, |ui| {
+                ui.label("Mode");
+                ComboBox::from_id_source("stereo_mode")
+                    .selected_text(settings.stereo_mode.to_string())
+                    .show_ui(ui, |ui| {
+                        for mode in stereo_modes() {
+                            ui.selectable_value(&mut settings.stereo_mode, mode, mode.to_string());
+                        }
+                    });
+                ui.end_row();
+                ui.label("Eye separation");
+                ui.add(
+                    DragValue::new(&mut settings.stereo_iod)
+                        .speed(0.01)
+                        .clamp_range(0.0..=5.0),
+                );
+                ui.end_row();
+                ui.label("Convergence distance");
+                ui.add(
+                    DragValue::new(&mut settings.stereo_convergence)
+                        .speed(0.5)
+                        .clamp_range(1.0..=1000.0),
+                );
+                ui.end_row();
+            });
+            ui.separator();
+
+            ui.heading(RichText::new("Graphics").color(Color32::GREEN));
+            Grid::new("graphics").show(ui, |ui| {
+                ui.label("Quality");
+                ComboBox::from_id_source("graphics_quality")
+                    .selected_text(settings.graphics_quality.to_string())
+                    .show_ui(ui, |ui| {
+                        for quality in GraphicsQuality::ALL {
+                            ui.selectable_value(
+                                &mut settings.graphics_quality,
+                                quality,
+                                quality.to_string(),
+                            );
+                        }
+                    });
+                // `Graphics::apply` does all of it at once, in this frame.
+                ui.label(
+                    RichText::new("Changing this regenerates the sky and planets, so the game stops for a moment.")
+                        .small(),
+                );
+                ui.end_row();
+            });
+            ui.separator();
+
+            ui.label(RichText::new(&page.status).color(Color32::GREEN));
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    page.status = match settings.save() {
+                        Ok(()) => format!("Saved to {:?}", Settings::path()),
+                        Err(error) => format!("Could not save: {error}"),
+                    };
+                }
+                if ui.button("Reset to defaults").clicked() {
+                    settings = Settings::default();
+                }
+                if ui.button("Back").clicked() {
+                    next_state.set(AppState::Help);
+                }
+            });
+        });
+
+    // Only touch the configs when something changed, so nothing else sees a change every frame.
+    if settings != current {
+        settings.apply(&mut physics, &mut spacecraft);
+        if settings.graphics_quality != *quality {
+            *quality = settings.graphics_quality;
+            graphics.apply(*quality);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_settings_parse_back() {
+        let saved = Settings {
+            sims_per_frame: 3,
+            trails: true,
+            impact_scale: 0.25,
+            recoil: 0.0,
+            projectile_radius: 0.125,
+            stereo_mode: StereoMode::Off.next().next(),
+            stereo_iod: 1.5,
+            stereo_convergence: 42.0,
+            graphics_quality: GraphicsQuality::High,
+        };
+        assert_ne!(saved, Settings::default());
+
+        let mut settings = Settings::default();
+        settings.parse(&saved.to_string());
+        assert_eq!(settings, saved);
+    }
+
+    #[test]
+    fn unknown_and_malformed_lines_are_skipped() {
+        let mut settings = Settings::default();
+        settings.parse(
+            "
+            # a comment
+            sims_per_frame 4
+            sims_per_frame many
+            trails maybe
+            impact_scale
+            stereo_mode Hologram
+            graphics_quality ultra
+            warp_drive on
+            recoil 0.5
+            ",
+        );
+        assert_eq!(
+            settings,
+            Settings {
+                sims_per_frame: 4,
+                recoil: 0.5,
+                ..Settings::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use bevy::prelude::*;
+use bevy::render::render_resource::{
+    Extent3d, TextureDimension, TextureFormat, TextureViewDescriptor, TextureViewDimension,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::noise::{fbm, smoothstep};
+
+#[derive(Resource)]
+pub struct SkyConfig {
+    /// The same seed always makes the same sky.
+    pub seed: u64,
+    /// Width (and height) of each face of the cubemap, in pixels.
+    pub resolution: u32,
+    pub star_count: usize,
+    /// Zero turns the nebulae off.
+    pub nebula_brightness: f32,
+    /// Zero turns the band of the galaxy off.
+    pub band_brightness: f32,
+    /// The band runs around the sky at right angles to this.
+    pub band_normal: Vec3,
+}
+
+impl Default for SkyConfig {
+    fn default() -> Self {
+        Self {
+            seed: 1,
+            resolution: 512,
+            star_count: 6000,
+            nebula_brightness: 0.15,
+            band_brightness: 0.08,
+            band_normal: Vec3::new(0.3, 1.0, 0.2).normalize(),
+        }
+    }
+}
+
+const BAND_COLOR: Vec3 = Vec3::new(1.0, 0.9, 0.75);
+// Nebulae shade from one of these to the other.
+const NEBULA_COLORS: [Vec3; 2] = [Vec3::new(0.25, 0.1, 0.6), Vec3::new(0.8, 0.15, 0.35)];
+
+/// A cubemap of stars, nebulae and the band of the galaxy, for `Skybox`.
+pub fn skybox_image(config: &SkyConfig) -> Image {
+    let size = config.resolution as usize;
+    let mut texels = vec![Vec3::ZERO; size * size * 6];
+
+    for (index, texel) in texels.iter_mut().enumerate() {
+        let face = index / (size * size);
+        let (x, y) = (index % size, index / size % size);
+        let u = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+        let v = (y as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+        let direction = face_to_direction(face, u, v);
+        let band = band_density(config, direction);
+        let glow = band * (0.4 + 0.6 * fbm(config.seed, direction * 6.0, 3));
+        // Wisps: only the tops of the noise show, and mostly near the band.
+        let wisps = smoothstep(0.45, 0.8, fbm(config.seed + 1, direction * 3.0, 5));
+        let hue = fbm(config.seed + 2, direction * 1.5, 2);
+        let nebula = NEBULA_COLORS[0].lerp(NEBULA_COLORS[1], hue) * wisps * (0.3 + band);
+        *texel = BAND_COLOR * glow * config.band_brightness + nebula * config.nebula_brightness;
+    }
+
+    // Stars crowd into the band, like they do in the sky.
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut placed = 0;
+    while placed < config.star_count {
+        let direction = Vec3::new(
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+        );
+        let length = direction.length();
+        if !(f32::EPSILON..=1.0).contains(&length) {
+            continue;
+        }
+        let direction = direction / length;
+        if rng.gen::<f32>() > 0.25 + 0.75 * band_density(config, direction) {
+            continue;
+        }
+        placed += 1;
+        // Lots of faint stars, a few bright ones.
+        let brightness = 0.15 + rng.gen::<f32>().powf(8.0) * 2.0;
+        let color = star_color(rng.gen()) * brightness;
+        let (face, u, v) = direction_to_face(direction);
+        let to_texel = |t: f32| (t + 1.0) / 2.0 * size as f32;
+        let (x, y) = (to_texel(u), to_texel(v));
+        // Bright stars bleed into the texels around them.
+        let spread = if brightness > 1.0 { 1 } else { 0 };
+        for dy in -spread..=spread {
+            for dx in -spread..=spread {
+                let (tx, ty) = (x as i32 + dx, y as i32 + dy);
+                if tx < 0 || ty < 0 || tx >= size as i32 || ty >= size as i32 {
+                    continue;
+                }
+                let falloff = if dx == 0 && dy == 0 { 1.0 } else { 0.15 };
+                texels[face * size * size + ty as usize * size + tx as usize] += color * falloff;
+            }
+        }
+    }
+
+    let data = texels
+        .iter()
+        .flat_map(|texel| {
+            let texel = texel.min(Vec3::ONE);
+            Color::rgb_linear(texel.x, texel.y, texel.z).as_rgba_u8()
+        })
+        .collect();
+    let mut image = Image::new(
+        Extent3d {
+            width: config.resolution,
+            height: config.resolution,
+            depth_or_array_layers: 6,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    image.texture_view_descriptor = Some(TextureViewDescriptor {
+        dimension: Some(TextureViewDimension::Cube),
+        ..default()
+    });
+    image
+}
+
+// The skybox shader looks things up with z flipped, so this does too. Faces go +x, -x, +y, -y,
+// +z, -z, with `u` to the right and `v` down each face.
+fn face_to_direction(face: usize, u: f32, v: f32) -> Vec3 {
+    let cube = match face {
+        0 => Vec3::new(1.0, -v, -u),
+        1 => Vec3::new(-1.0, -v, u),
+        2 => Vec3::new(u, 1.0, v),
+        3 => Vec3::new(u, -1.0, -v),
+        4 => Vec3::new(u, -v, 1.0),
+        _ => Vec3::new(-u, -v, -1.0),
+    };
+    (cube * Vec3::new(1.0, 1.0, -1.0)).normalize()
+}
+
+fn direction_to_face(direction: Vec3) -> (usize, f32, f32) {
+    let Vec3 { x, y, z } = direction * Vec3::new(1.0, 1.0, -1.0);
+    let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+    if ax >= ay && ax >= az {
+        if x > 0.0 {
+            (0, -z / ax, -y / ax)
+        } else {
+            (1, z / ax, -y / ax)
+        }
+    } else if ay >= az {
+        if y > 0.0 {
+            (2, x / ay, z / ay)
+        } else {
+            (3, x / ay, -z / ay)
+        }
+    } else if z > 0.0 {
+        (4, x / az, -y / az)
+    } else {
+        (5, -x / az, -y / az)
+    }
+}
+
+// 1.0 in the middle of the band, falling off to nothing either side.
+fn band_density(config: &SkyConfig, direction: Vec3) -> f32 {
+    let height = direction.dot(config.band_normal) / 0.2;
+    (-height * height).exp()
+}
+
+// From red dwarfs, through white, to blue giants.
+fn star_color(temperature: f32) -> Vec3 {
+    const COOL: Vec3 = Vec3::new(1.0, 0.6, 0.4);
+    const HOT: Vec3 = Vec3::new(0.6, 0.75, 1.0);
+    if temperature < 0.5 {
+        COOL.lerp(Vec3::ONE, temperature * 2.0)
+    } else {
+        Vec3::ONE.lerp(HOT, temperature * 2.0 - 1.0)
+    }
+}
@@ -0,0 +1,383 @@
+use bevy::audio::{AddAudioSource, AudioPlugin, Decodable, Source, SpatialScale, Volume};
+use bevy::prelude::*;
+use bevy::reflect::TypePath;
+use rand::Rng;
+use std::f32::consts::TAU;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::craft::{
+    fire_projectiles, FireProjectileEvent, ProjectileCollisionEvent, ProjectileKind, Spacecraft,
+    Weapon,
+};
+use crate::physics::{Momentum, PlanetCollisionEvent};
+
+const SAMPLE_RATE: u32 = 44_100;
+// Sounds are at full volume within 10 units, and fall off with the square of the distance beyond.
+const SPATIAL_SCALE: f32 = 0.1;
+// Merges of this much mass sound at their natural pitch. Bigger ones are deeper.
+const REFERENCE_MASS: f32 = 100.0;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SoundBackend {
+    /// Through bevy's audio output, positioned relative to the spacecraft.
+    #[default]
+    Speakers,
+    /// Nowhere. For tests, headless runs and machines without an audio device.
+    Null,
+}
+
+#[derive(Resource)]
+pub struct SoundConfig {
+    /// `Speakers` falls back to `Null` without bevy's `AudioPlugin`.
+    pub backend: SoundBackend,
+    /// All of the volumes are from 0.0 (silent) to 1.0.
+    pub master_volume: f32,
+    pub effects_volume: f32,
+    pub ambient_volume: f32,
+}
+
+impl Default for SoundConfig {
+    fn default() -> Self {
+        Self {
+            backend: SoundBackend::Speakers,
+            master_volume: 0.8,
+            effects_volume: 1.0,
+            ambient_volume: 0.3,
+        }
+    }
+}
+
+/// A sound generated on startup, rather than loaded from a file. Mono.
+#[derive(Asset, TypePath, Clone)]
+pub struct Synth {
+    samples: Arc<[f32]>,
+}
+
+pub struct SynthDecoder {
+    samples: Arc<[f32]>,
+    next: usize,
+}
+
+impl Iterator for SynthDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.samples.get(self.next).copied();
+        self.next += 1;
+        sample
+    }
+}
+
+impl Source for SynthDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(Duration::from_secs_f32(
+            self.samples.len() as f32 / SAMPLE_RATE as f32,
+        ))
+    }
+}
+
+impl Decodable for Synth {
+    type DecoderItem = f32;
+    type Decoder = SynthDecoder;
+
+    fn decoder(&self) -> SynthDecoder {
+        SynthDecoder {
+            samples: self.samples.clone(),
+            next: 0,
+        }
+    }
+}
+
+// `seconds` of whatever `wave` makes of the time since the start.
+fn synthesize(seconds: f32, mut wave: impl FnMut(f32) -> f32) -> Synth {
+    let count = (seconds * SAMPLE_RATE as f32) as usize;
+    Synth {
+        samples: (0..count)
+            .map(|i| wave(i as f32 / SAMPLE_RATE as f32).clamp(-1.0, 1.0))
+            .collect(),
+    }
+}
+
+// A chirp, falling from high to low.
+fn fire_synth() -> Synth {
+    let mut phase = 0.0;
+    synthesize(0.18, |t| {
+        let frequency = 300.0 + 900.0 * (-t * 25.0).exp();
+        phase += frequency / SAMPLE_RATE as f32;
+        (phase * TAU).sin() * (-t * 18.0).exp() * 0.5
+    })
+}
+
+// A thud under a crackle.
+fn impact_synth() -> Synth {
+    let mut rng = rand::thread_rng();
+    let mut crackle = 0.0;
+    synthesize(0.4, |t| {
+        // Noise, smoothed so it isn't all hiss.
+        crackle += (rng.gen_range(-1.0..1.0) - crackle) * 0.2;
+        let thud = (t * 80.0 * TAU).sin() * (-t * 12.0).exp();
+        (thud * 0.6 + crackle * (-t * 20.0).exp()) * 0.8
+    })
+}
+
+// A long, low rumble.
+fn merge_synth() -> Synth {
+    let mut rng = rand::thread_rng();
+    let mut rumble = 0.0;
+    synthesize(1.8, |t| {
+        rumble += (rng.gen_range(-1.0..1.0) - rumble) * 0.02;
+        let swell = (t * 20.0).min(1.0) * (-t * 2.0).exp();
+        let tone = (t * 55.0 * TAU).sin() + (t * 41.0 * TAU).sin() * 0.5;
+        (tone * 0.4 + rumble * 3.0) * swell
+    })
+}
+
+// Beating low tones, slowly swelling and fading. Every frequency here fits a whole number of
+// cycles into the length of the loop, so it loops without a click.
+fn drone_synth() -> Synth {
+    synthesize(8.0, |t| {
+        let tones = [(55.0, 1.0), (55.375, 0.8), (82.5, 0.4), (110.25, 0.2)];
+        let tone: f32 = tones
+            .iter()
+            .map(|(frequency, level)| (t * frequency * TAU).sin() * level)
+            .sum();
+        let swell = 0.7 + 0.3 * (t * 0.25 * TAU).sin();
+        tone * swell * 0.2
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoundKind {
+    Fire,
+    Impact,
+    Merge,
+}
+
+#[derive(Resource)]
+pub struct SoundBank {
+    fire: Handle<Synth>,
+    impact: Handle<Synth>,
+    merge: Handle<Synth>,
+    drone: Handle<Synth>,
+}
+
+impl SoundBank {
+    fn get(&self, kind: SoundKind) -> Handle<Synth> {
+        match kind {
+            SoundKind::Fire => self.fire.clone(),
+            SoundKind::Impact => self.impact.clone(),
+            SoundKind::Merge => self.merge.clone(),
+        }
+    }
+}
+
+/// Play a sound at `position`. Whether anyone hears it is up to the backend.
+#[derive(Event, Debug)]
+pub struct PlaySound {
+    pub kind: SoundKind,
+    pub position: Vec3,
+    /// 1.0 is the sound's natural pitch. Higher is also shorter.
+    pub pitch: f32,
+    pub volume: f32,
+}
+
+/// Marks the ambient drone, which plays on a loop for as long as the game runs.
+#[derive(Component)]
+pub struct Drone;
+
+pub struct Sound;
+
+impl Plugin for Sound {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SoundConfig>()
+            .add_event::<PlaySound>()
+            .add_systems(
+                Update,
+                (
+                    // While the gun still says whether it is ready to fire.
+                    fire_sounds
+                        .before(fire_projectiles)
+                        .before(play_sounds)
+                        .before(discard_sounds),
+                    impact_sounds.before(play_sounds).before(discard_sounds),
+                    merge_sounds.before(play_sounds).before(discard_sounds),
+                    discard_sounds.run_if(backend_is(SoundBackend::Null)),
+                ),
+            );
+        if app.is_plugin_added::<AudioPlugin>() {
+            app.add_audio_source::<Synth>()
+                .insert_resource(SpatialScale::new(SPATIAL_SCALE))
+                .add_systems(Startup, sound_setup)
+                .add_systems(
+                    Update,
+                    (
+                        attach_listener,
+                        play_sounds.run_if(backend_is(SoundBackend::Speakers)),
+                        adjust_drone,
+                    ),
+                );
+        } else {
+            app.world.resource_mut::<SoundConfig>().backend = SoundBackend::Null;
+        }
+    }
+}
+
+fn backend_is(backend: SoundBackend) -> impl Fn(Res<SoundConfig>) -> bool {
+    move |config| config.backend == backend
+}
+
+pub fn sound_setup(
+    mut commands: Commands,
+    mut synths: ResMut<Assets<Synth>>,
+    config: Res<SoundConfig>,
+) {
+    let sounds = SoundBank {
+        fire: synths.add(fire_synth()),
+        impact: synths.add(impact_synth()),
+        merge: synths.add(merge_synth()),
+        drone: synths.add(drone_synth()),
+    };
+    if config.backend == SoundBackend::Speakers {
+        commands
+            .spawn(AudioSourceBundle {
+                source: sounds.drone.clone(),
+                settings: PlaybackSettings::LOOP.with_volume(Volume::new_absolute(
+                    config.ambient_volume * config.master_volume,
+                )),
+            })
+            .insert(Drone);
+    }
+    commands.insert_resource(sounds);
+}
+
+/// We hear from wherever the spacecraft is, whichever camera we're looking through.
+pub fn attach_listener(
+    mut commands: Commands,
+    spacecraft_query: Query<Entity, (With<Spacecraft>, Without<SpatialListener>)>,
+) {
+    for spacecraft in spacecraft_query.iter() {
+        commands
+            .entity(spacecraft)
+            .insert(SpatialListener::new(0.5));
+    }
+}
+
+pub fn play_sounds(
+    mut commands: Commands,
+    mut sound_events: EventReader<PlaySound>,
+    sounds: Res<SoundBank>,
+    config: Res<SoundConfig>,
+) {
+    for event in sound_events.read() {
+        let volume = event.volume * config.effects_volume * config.master_volume;
+        if volume <= 0.0 {
+            continue;
+        }
+        commands.spawn((
+            AudioSourceBundle {
+                source: sounds.get(event.kind),
+                settings: PlaybackSettings {
+                    volume: Volume::new_absolute(volume),
+                    speed: event.pitch,
+                    spatial: true,
+                    ..PlaybackSettings::DESPAWN
+                },
+            },
+            TransformBundle::from_transform(Transform::from_translation(event.position)),
+        ));
+    }
+}
+
+pub fn discard_sounds(mut sound_events: EventReader<PlaySound>) {
+    for event in sound_events.read() {
+        trace!("Not playing {event:?}");
+    }
+}
+
+pub fn adjust_drone(config: Res<SoundConfig>, drone_query: Query<&AudioSink, With<Drone>>) {
+    if !config.is_changed() {
+        return;
+    }
+    for sink in drone_query.iter() {
+        sink.set_volume(config.ambient_volume * config.master_volume);
+    }
+}
+
+fn kind_pitch(kind: ProjectileKind) -> f32 {
+    match kind {
+        ProjectileKind::Push => 1.0,
+        ProjectileKind::Pull => 0.8,
+        ProjectileKind::Mass => 0.6,
+        ProjectileKind::Spread => 1.25,
+    }
+}
+
+// One sound per volley, however many pellets are in it, and none for a gun that can't fire.
+pub fn fire_sounds(
+    mut fire_projectile_events: EventReader<FireProjectileEvent>,
+    spacecraft_query: Query<(&Transform, &Weapon), With<Spacecraft>>,
+    mut sound_events: EventWriter<PlaySound>,
+) {
+    if fire_projectile_events.read().next().is_none() {
+        return;
+    }
+    for (transform, weapon) in spacecraft_query.iter() {
+        if weapon.ready() {
+            sound_events.send(PlaySound {
+                kind: SoundKind::Fire,
+                position: transform.translation,
+                pitch: kind_pitch(weapon.kind),
+                volume: 0.6,
+            });
+        }
+    }
+}
+
+pub fn impact_sounds(
+    mut projectile_events: EventReader<ProjectileCollisionEvent>,
+    planet_query: Query<&Transform, With<Momentum>>,
+    mut sound_events: EventWriter<PlaySound>,
+) {
+    for event in projectile_events.read() {
+        if let Ok(transform) = planet_query.get(event.planet) {
+            sound_events.send(PlaySound {
+                kind: SoundKind::Impact,
+                position: transform.translation + event.local_impact_site,
+                pitch: kind_pitch(event.kind),
+                volume: 1.0,
+            });
+        }
+    }
+}
+
+/// Big merges rumble deeper than small ones.
+pub fn merge_sounds(
+    mut planet_events: EventReader<PlanetCollisionEvent>,
+    planet_query: Query<(&Transform, &Momentum)>,
+    mut sound_events: EventWriter<PlaySound>,
+) {
+    for &PlanetCollisionEvent(e0, e1) in planet_events.read() {
+        if let Ok([(t0, m0), (t1, m1)]) = planet_query.get_many([e0, e1]) {
+            let mass = m0.mass + m1.mass;
+            sound_events.send(PlaySound {
+                kind: SoundKind::Merge,
+                position: (t0.translation * m0.mass + t1.translation * m1.mass) / mass,
+                pitch: (REFERENCE_MASS / mass).powf(1.0 / 3.0).clamp(0.3, 2.0),
+                volume: 1.0,
+            });
+        }
+    }
+}